@@ -1,7 +1,15 @@
+use std::{fmt, path::Path};
+
+use anyhow::Context;
 use chrono::{DateTime, Utc};
 use discord_rich_presence::activity::ActivityType;
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
+use thiserror::Error;
+
+/// Longest image key or URL discord accepts
+pub const MAX_IMAGE_KEY_LENGTH: usize = 256;
+const IMAGE_URL_SCHEMES: [&str; 2] = ["https://", "mp:"];
 
 #[repr(u8)]
 #[derive(Default, Clone, Debug, Copy, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
@@ -11,6 +19,79 @@ pub enum TimestampType {
 	LocalTime = 2,
 	Custom = 3,
 	SinceLastUpdate = 4,
+	/// Counts down to `end_timestamp`
+	EndsAt = 5,
+	/// Counts down `countdown` seconds from when the activity is sent
+	Countdown = 6,
+}
+
+pub static TIMESTAMP_TYPES: [TimestampType; 6] = [
+	TimestampType::SinceLastUpdate,
+	TimestampType::SinceStart,
+	TimestampType::LocalTime,
+	TimestampType::Custom,
+	TimestampType::EndsAt,
+	TimestampType::Countdown,
+];
+
+#[repr(u8)]
+#[derive(Default, Clone, Debug, Copy, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
+pub enum ImageSource {
+	/// Name of an asset uploaded to the application
+	#[default]
+	Asset = 0,
+	/// https or `mp:` media proxy link
+	Url = 1,
+}
+
+pub static IMAGE_SOURCES: [ImageSource; 2] = [ImageSource::Asset, ImageSource::Url];
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ImageUrlError {
+	#[error("Image URLs have to start with https:// or mp:")]
+	Scheme,
+	#[error("Image URL is missing a host")]
+	NoHost,
+	#[error("Image URLs cant contain whitespace")]
+	Whitespace,
+	#[error("Image URLs can be at most {MAX_IMAGE_KEY_LENGTH} characters long")]
+	TooLong,
+}
+
+impl fmt::Display for TimestampType {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			TimestampType::SinceStart => write!(f, "Since start"),
+			TimestampType::LocalTime => write!(f, "Local time"),
+			TimestampType::Custom => write!(f, "Custom"),
+			TimestampType::SinceLastUpdate => write!(f, "Since last presence update"),
+			TimestampType::EndsAt => write!(f, "Ends at"),
+			TimestampType::Countdown => write!(f, "Countdown"),
+		}
+	}
+}
+
+impl ImageSource {
+	/// Guesses the source of a bare key from its scheme
+	pub fn of(key: &str) -> ImageSource {
+		if IMAGE_URL_SCHEMES
+			.iter()
+			.any(|scheme| key.starts_with(scheme))
+		{
+			ImageSource::Url
+		} else {
+			ImageSource::Asset
+		}
+	}
+}
+
+impl fmt::Display for ImageSource {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			ImageSource::Asset => write!(f, "Asset"),
+			ImageSource::Url => write!(f, "URL"),
+		}
+	}
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -29,8 +110,19 @@ pub struct Activity {
 	pub timestamp_type: TimestampType,
 	#[serde(with = "crp_format")]
 	pub custom_timestamp: Option<DateTime<Utc>>,
+	#[serde(default, with = "crp_format")]
+	pub end_timestamp: Option<DateTime<Utc>>,
+	/// Where the bar of `EndsAt` starts, only known to automations like the calendar
+	#[serde(skip)]
+	pub start_timestamp: Option<DateTime<Utc>>,
+	#[serde(default)]
+	pub countdown: Option<u64>,
 	pub large_key: Option<String>,
 	pub small_key: Option<String>,
+	#[serde(default)]
+	pub large_source: ImageSource,
+	#[serde(default)]
+	pub small_source: ImageSource,
 	pub small_text: Option<String>,
 	pub large_text: Option<String>,
 	pub button1_text: Option<String>,
@@ -53,6 +145,8 @@ impl Default for Activity {
 			timestamp_type: Default::default(),
 			large_key: Default::default(),
 			small_key: Default::default(),
+			large_source: Default::default(),
+			small_source: Default::default(),
 			small_text: Default::default(),
 			large_text: Default::default(),
 			button1_text: Default::default(),
@@ -60,10 +154,55 @@ impl Default for Activity {
 			button1_url: Default::default(),
 			button2_url: Default::default(),
 			custom_timestamp: Default::default(),
+			end_timestamp: Default::default(),
+			start_timestamp: Default::default(),
+			countdown: Default::default(),
 		}
 	}
 }
 
+impl Activity {
+	pub async fn read(path: impl AsRef<Path>) -> anyhow::Result<Activity> {
+		let data = tokio::fs::read(path).await?;
+
+		serde_xml_rs::from_reader::<Activity, _>(data.as_slice())
+			.context("Failed to parse activity file")
+	}
+	/// Image keys of both slots with the source they are in
+	pub fn images(&self) -> [(ImageSource, Option<&str>); 2] {
+		[
+			(self.large_source, self.large_key.as_deref()),
+			(self.small_source, self.small_key.as_deref()),
+		]
+	}
+	/// Name of the `index`th entry in a list of frames or rules, the details if there are any
+	pub fn label(&self, kind: &str, index: usize) -> String {
+		self.details
+			.clone()
+			.unwrap_or_else(|| format!("{kind} {}", index + 1))
+	}
+}
+
+pub fn validate_image_url(url: &str) -> Result<(), ImageUrlError> {
+	let Some(rest) = IMAGE_URL_SCHEMES
+		.iter()
+		.find_map(|scheme| url.strip_prefix(scheme))
+	else {
+		return Err(ImageUrlError::Scheme);
+	};
+	if url.chars().any(char::is_whitespace) {
+		return Err(ImageUrlError::Whitespace);
+	}
+	if rest.split('/').next().is_none_or(str::is_empty) {
+		return Err(ImageUrlError::NoHost);
+	}
+	if url.len() > MAX_IMAGE_KEY_LENGTH {
+		return Err(ImageUrlError::TooLong);
+	}
+
+	Ok(())
+}
+
 mod crp_format {
 	use chrono::{DateTime, NaiveDateTime, Utc};
 	use serde::{self, Deserialize, Deserializer, Serializer};
@@ -0,0 +1,296 @@
+use std::{net::Ipv4Addr, time::Duration};
+
+use anyhow::anyhow;
+use iced::futures::channel::mpsc::UnboundedSender;
+use log::{error, info};
+use tokio::{
+	io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+	net::{TcpListener, TcpStream, tcp::OwnedReadHalf},
+	task, time,
+};
+
+use crate::control::{ControlMessage, ControlRequest, ControlResponse, dispatch};
+
+const MAX_BODY_SIZE: usize = 64 * 1024;
+const MAX_HEADER_SIZE: u64 = 8 * 1024;
+const MAX_HEADERS: usize = 64;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// what browser extensions send as their origin, web pages get turned away
+const EXTENSION_ORIGINS: [&str; 3] = [
+	"chrome-extension://",
+	"moz-extension://",
+	"safari-web-extension://",
+];
+
+pub struct Api {
+	send: UnboundedSender<ControlMessage>,
+	port: u16,
+}
+
+struct Request {
+	method: String,
+	path: String,
+	host: Option<String>,
+	origin: Option<String>,
+	body: Vec<u8>,
+}
+
+impl Api {
+	pub fn spawn_thread(port: u16, send: UnboundedSender<ControlMessage>) {
+		task::spawn(async move {
+			let listener = match TcpListener::bind((Ipv4Addr::LOCALHOST, port)).await {
+				Ok(listener) => listener,
+				Err(err) => {
+					error!("Failed to start http api on port {port}: {err}");
+					return;
+				}
+			};
+			info!("Http api listening on 127.0.0.1:{port}");
+
+			loop {
+				let stream = match listener.accept().await {
+					Ok((stream, _)) => stream,
+					Err(err) => {
+						error!("{err}");
+						continue;
+					}
+				};
+
+				let state = Api {
+					send: send.clone(),
+					port,
+				};
+				task::spawn(async move {
+					if let Err(err) = state.event_loop(stream).await {
+						error!("{err}");
+					}
+				});
+			}
+		});
+	}
+
+	async fn event_loop(mut self, stream: TcpStream) -> anyhow::Result<()> {
+		let (read, mut write) = stream.into_split();
+		let mut reader = BufReader::new(read);
+
+		let request = time::timeout(REQUEST_TIMEOUT, read_request(&mut reader))
+			.await
+			.unwrap_or_else(|_| Err(anyhow!("Timed out reading the request")));
+		let (status, response) = match request {
+			Ok(request) => self.handle(request).await?,
+			Err(err) => (400, ControlResponse::error(err)),
+		};
+
+		let body = serde_json::to_vec(&response)?;
+		let head = format!(
+			"HTTP/1.1 {status} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+			reason(status),
+			body.len()
+		);
+		write.write_all(head.as_bytes()).await?;
+		write.write_all(&body).await?;
+		write.shutdown().await?;
+
+		Ok(())
+	}
+
+	async fn handle(&mut self, request: Request) -> anyhow::Result<(u16, ControlResponse)> {
+		if !request
+			.host
+			.as_deref()
+			.is_some_and(|v| self.is_local_host(v))
+		{
+			return Ok((403, ControlResponse::error("Host not allowed")));
+		}
+		// browsers always send it cross-site, even for the simple requests that skip the preflight
+		if !request.origin.as_deref().is_none_or(is_extension_origin) {
+			return Ok((403, ControlResponse::error("Origin not allowed")));
+		}
+
+		let control_request = match (request.method.as_str(), request.path.as_str()) {
+			("GET", "/activity") => ControlRequest::Get,
+			("PUT", "/activity") => match serde_json::from_slice(&request.body) {
+				Ok(activity) => ControlRequest::Set { activity },
+				Err(err) => return Ok((400, ControlResponse::error(err))),
+			},
+			("PATCH", "/activity") => match serde_json::from_slice(&request.body) {
+				Ok(activity) => ControlRequest::Patch { activity },
+				Err(err) => return Ok((400, ControlResponse::error(err))),
+			},
+			("DELETE", "/activity") => ControlRequest::Clear,
+			("POST", "/connect") => ControlRequest::Connect,
+			("POST", "/disconnect") => ControlRequest::Disconnect,
+			(_, "/activity" | "/connect" | "/disconnect") => {
+				return Ok((405, ControlResponse::error("Method not allowed")));
+			}
+			_ => return Ok((404, ControlResponse::error("Not found"))),
+		};
+
+		let response = dispatch(&mut self.send, control_request).await?;
+		let status = if response.ok { 200 } else { 400 };
+
+		Ok((status, response))
+	}
+
+	// rejects dns rebinding attempts, only requests addressed to loopback get through
+	fn is_local_host(&self, host: &str) -> bool {
+		let name = match host.rsplit_once(':') {
+			Some((name, port)) if port == self.port.to_string() => name,
+			Some(_) => return false,
+			None => host,
+		};
+
+		matches!(name, "127.0.0.1" | "localhost")
+	}
+}
+
+fn is_extension_origin(origin: &str) -> bool {
+	EXTENSION_ORIGINS
+		.iter()
+		.any(|scheme| origin.strip_prefix(scheme).is_some_and(|id| !id.is_empty()))
+}
+
+async fn read_request(reader: &mut BufReader<OwnedReadHalf>) -> anyhow::Result<Request> {
+	let mut head = (&mut *reader).take(MAX_HEADER_SIZE);
+	let mut line = String::new();
+	head.read_line(&mut line).await?;
+	let mut parts = line.split_whitespace();
+	let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+		return Err(anyhow!("Malformed request line"));
+	};
+	let method = method.to_string();
+	let path = target.split('?').next().unwrap_or_default().to_string();
+
+	let mut host = None;
+	let mut origin = None;
+	let mut content_length = 0;
+	for _ in 0..MAX_HEADERS {
+		line.clear();
+		head.read_line(&mut line).await?;
+		if head.limit() == 0 {
+			return Err(anyhow!("Headers too large"));
+		}
+		let header = line.trim_end();
+		if header.is_empty() {
+			break;
+		}
+
+		let Some((name, value)) = header.split_once(':') else {
+			return Err(anyhow!("Malformed header"));
+		};
+		let value = value.trim();
+		if name.eq_ignore_ascii_case("host") {
+			host = Some(value.to_string());
+		} else if name.eq_ignore_ascii_case("origin") {
+			origin = Some(value.to_string());
+		} else if name.eq_ignore_ascii_case("content-length") {
+			content_length = value.parse::<usize>()?;
+		}
+	}
+
+	if content_length > MAX_BODY_SIZE {
+		return Err(anyhow!("Body too large"));
+	}
+	let mut body = vec![0; content_length];
+	reader.read_exact(&mut body).await?;
+
+	Ok(Request {
+		method,
+		path,
+		host,
+		origin,
+		body,
+	})
+}
+
+fn reason(status: u16) -> &'static str {
+	match status {
+		200 => "OK",
+		400 => "Bad Request",
+		403 => "Forbidden",
+		404 => "Not Found",
+		405 => "Method Not Allowed",
+		_ => "",
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use iced::futures::{StreamExt, channel::mpsc};
+
+	use super::*;
+	use crate::activity::Activity;
+
+	const PORT: u16 = 4242;
+
+	fn request(origin: Option<&str>) -> Request {
+		Request {
+			method: String::from("GET"),
+			path: String::from("/activity"),
+			host: Some(format!("127.0.0.1:{PORT}")),
+			origin: origin.map(str::to_string),
+			body: Vec::new(),
+		}
+	}
+
+	/// An api whose requests get answered like the app would, and how many reached it
+	fn api() -> (Api, task::JoinHandle<usize>) {
+		let (send, mut recv) = mpsc::unbounded::<ControlMessage>();
+		let app = task::spawn(async move {
+			let mut handled = 0;
+			while let Some(msg) = recv.next().await {
+				let _ = msg
+					.reply
+					.unbounded_send(ControlResponse::accepted(&Activity::default()));
+				handled += 1;
+			}
+			handled
+		});
+
+		(Api { send, port: PORT }, app)
+	}
+
+	#[tokio::test]
+	async fn extension_origins_are_accepted() {
+		let (mut api, app) = api();
+
+		for origin in [
+			"chrome-extension://abcdefghijklmnopabcdefghijklmnop",
+			"moz-extension://0b9c5a56-2c4c-4a1d-9b3e-1e4c5d6f7a8b",
+		] {
+			let (status, response) = api.handle(request(Some(origin))).await.unwrap();
+			assert_eq!(status, 200, "{origin}");
+			assert!(response.ok);
+		}
+		drop(api);
+		assert_eq!(app.await.unwrap(), 2);
+	}
+
+	#[tokio::test]
+	async fn requests_without_origin_are_accepted() {
+		let (mut api, app) = api();
+
+		let (status, _) = api.handle(request(None)).await.unwrap();
+		assert_eq!(status, 200);
+		drop(api);
+		assert_eq!(app.await.unwrap(), 1);
+	}
+
+	#[tokio::test]
+	async fn web_origins_are_rejected() {
+		let (mut api, app) = api();
+
+		for origin in [
+			"https://example.com",
+			"http://localhost:3000",
+			"null",
+			"chrome-extension://",
+		] {
+			let (status, response) = api.handle(request(Some(origin))).await.unwrap();
+			assert_eq!(status, 403, "{origin}");
+			assert!(!response.ok);
+		}
+		drop(api);
+		assert_eq!(app.await.unwrap(), 0);
+	}
+}
@@ -1,8 +1,22 @@
+use std::{collections::BTreeSet, path::PathBuf};
+
+use chrono::Weekday;
 use discord_rich_presence::activity::ActivityType;
+use iced::window;
 
 use crate::{
-	activity::{Activity, TimestampType},
+	activity::{Activity, ImageSource, TimestampType},
+	assets::Asset,
+	calendar::CalendarEvent,
+	control::ControlMessage,
+	detection::RunningProcesses,
+	focus::FocusedWindow,
+	generation::Generation,
+	idle::{AwayAction, IdleTime},
+	mpris::NowPlaying,
 	presence::PresenceThreadMessage,
+	schedule::TimeOfDay,
+	settings::{LogLevel, ThemeSetting},
 	tray::TrayMessage,
 };
 
@@ -11,27 +25,56 @@ use crate::{
 pub enum Message {
 	Presence(PresenceThreadMessage),
 	TrayMessage(TrayMessage),
+	Control(ControlMessage),
 	Error(String),
 	Activity(ActivityMsg),
+	Preset(PresetMsg),
+	Rotation(RotationMsg),
+	Pomodoro(PomodoroMsg),
+	Schedule(ScheduleMsg),
+	Calendar(CalendarMsg),
+	Away(AwayMsg),
+	Assets(AssetMsg),
+	Notification(NotificationMsg),
+	Idle(IdleTime),
+	Detection(DetectionMsg),
+	Focus(FocusMsg),
+	NowPlaying(NowPlaying),
+	Settings(SettingsMsg),
+	Window(window::Id, window::Event),
 	None,
 	Connect,
+	AutoConnect,
 	SetActivity,
 	Disconnect,
 	ChooseDate,
 	ChooseTime,
 	CancelTime,
 	CancelDate,
+	ChooseEndDate,
+	ChooseEndTime,
+	CancelEndDate,
+	CancelEndTime,
 	Exit,
 	OpenActivity,
 	SaveActivity,
 	LoadActivity(Activity),
+	NewActivity,
+	OpenSettings,
+	/// Redraws the preview
+	Tick,
 }
 
 #[allow(clippy::large_enum_variant)]
 pub enum MainThreadMessage {
 	Connect(String),
+	/// Like `Connect`, but retries in the background until discord is running
+	WaitForDiscord(String),
 	Disconnect,
 	SetActivity(Activity),
+	ClearActivity,
+	/// Whether placeholders of the shown activity get filled in again every minute
+	TemplateRefresh(bool),
 	Exit,
 }
 
@@ -46,14 +89,162 @@ pub enum ActivityMsg {
 	PartyMax(i32),
 	CustomDate(iced_aw::date_picker::Date),
 	CustomTime(iced_aw::time_picker::Time),
+	EndDate(iced_aw::date_picker::Date),
+	EndTime(iced_aw::time_picker::Time),
+	/// Minutes
+	Countdown(u64),
 	Button1Text(String),
 	Button2Text(String),
 	Button1URL(String),
 	Button2URL(String),
 	SmallImageText(String),
 	SmallImageKey(String),
+	SmallImageSource(ImageSource),
 	LargeImageText(String),
 	LargeImageKey(String),
+	LargeImageSource(ImageSource),
+}
+
+#[derive(Debug, Clone)]
+pub enum PresetMsg {
+	Select(usize),
+	Apply(usize),
+	Rename(String),
+	New,
+	Duplicate,
+	Delete,
+	Save,
+}
+
+#[derive(Debug, Clone)]
+pub enum RotationMsg {
+	Start,
+	Stop,
+	Toggle,
+	Next(Generation),
+	AddFrame,
+	RemoveFrame(usize),
+	MoveUp(usize),
+	Duration(usize, u64),
+}
+
+#[derive(Debug, Clone)]
+pub enum PomodoroMsg {
+	Start,
+	Stop,
+	Toggle,
+	Next(Generation),
+	FocusMinutes(u64),
+	BreakMinutes(u64),
+	LongBreakMinutes(u64),
+	Cycles(u64),
+	UseForFocus,
+	UseForBreak,
+	ResetActivities,
+}
+
+#[derive(Debug, Clone)]
+pub enum ScheduleMsg {
+	Enabled(bool),
+	/// Re-checks the schedule and re-arms the timer
+	Refresh,
+	Tick(Generation),
+	AddRule,
+	RemoveRule(usize),
+	MoveUp(usize),
+	ToggleDay(usize, Weekday),
+	Start(usize, TimeOfDay),
+	End(usize, TimeOfDay),
+}
+
+#[derive(Debug, Clone)]
+pub enum CalendarMsg {
+	Enabled(bool),
+	Path(String),
+	Browse,
+	Picked(String),
+	Redact(bool),
+	ImageKey(String),
+	Reload,
+	Loaded(Generation, Result<Vec<CalendarEvent>, String>),
+	Tick(Generation),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageSlot {
+	Large,
+	Small,
+}
+
+#[derive(Debug, Clone)]
+pub enum AssetMsg {
+	Loaded(String, Result<Vec<Asset>, String>),
+	Import,
+	Imported(String, Result<Vec<Asset>, String>),
+	/// Opens the picker, or closes it if it is already open for the slot
+	Toggle(ImageSlot),
+	Search(String),
+	Pick(String),
+	/// Downloads previews of image URLs once typing settled
+	FetchThumbnails(Generation),
+	Thumbnail(String, Option<PathBuf>),
+}
+
+#[derive(Debug, Clone)]
+pub enum AwayMsg {
+	Enabled(bool),
+	TimeoutMinutes(u64),
+	Action(AwayAction),
+	UseCurrent,
+	Reset,
+}
+
+#[derive(Debug, Clone)]
+pub enum DetectionMsg {
+	Running(BTreeSet<String>),
+	Enabled(bool),
+	AddRule,
+	RemoveRule(usize),
+	MoveUp(usize),
+	Processes(usize, String),
+}
+
+#[derive(Debug, Clone)]
+pub enum FocusMsg {
+	Window(FocusedWindow),
+	Enabled(bool),
+	AddRule,
+	RemoveRule(usize),
+	Class(usize, String),
+	Title(usize, String),
+	Details(usize, String),
+	State(usize, String),
+}
+
+#[derive(Debug, Clone)]
+pub enum SettingsMsg {
+	AutoConnect(bool),
+	StartMinimized(bool),
+	RememberWindow(bool),
+	DefaultTimestamp(TimestampType),
+	Theme(ThemeSetting),
+	LogLevel(LogLevel),
+	ApiEnabled(bool),
+	ApiPort(u16),
+	Autostart(bool),
+	/// The autostart entry was written or removed
+	AutostartSet(bool),
+	Mpris(bool),
+	DesktopNotifications(bool),
+	TemplateRefresh(bool),
+}
+
+#[derive(Debug, Clone)]
+pub enum NotificationMsg {
+	Dismiss(u64),
+	DismissAll,
+	Clear,
+	TogglePanel,
 }
 
 impl From<PresenceThreadMessage> for Message {
@@ -67,6 +258,102 @@ impl From<ActivityMsg> for Message {
 	}
 }
 
+impl From<PresetMsg> for Message {
+	fn from(val: PresetMsg) -> Self {
+		Message::Preset(val)
+	}
+}
+
+impl From<RotationMsg> for Message {
+	fn from(val: RotationMsg) -> Self {
+		Message::Rotation(val)
+	}
+}
+
+impl From<PomodoroMsg> for Message {
+	fn from(val: PomodoroMsg) -> Self {
+		Message::Pomodoro(val)
+	}
+}
+
+impl From<ScheduleMsg> for Message {
+	fn from(val: ScheduleMsg) -> Self {
+		Message::Schedule(val)
+	}
+}
+
+impl From<CalendarMsg> for Message {
+	fn from(val: CalendarMsg) -> Self {
+		Message::Calendar(val)
+	}
+}
+
+impl From<AssetMsg> for Message {
+	fn from(val: AssetMsg) -> Self {
+		Message::Assets(val)
+	}
+}
+
+impl From<AwayMsg> for Message {
+	fn from(val: AwayMsg) -> Self {
+		Message::Away(val)
+	}
+}
+
+impl From<NotificationMsg> for Message {
+	fn from(val: NotificationMsg) -> Self {
+		Message::Notification(val)
+	}
+}
+
+impl From<DetectionMsg> for Message {
+	fn from(val: DetectionMsg) -> Self {
+		Message::Detection(val)
+	}
+}
+
+impl From<RunningProcesses> for Message {
+	fn from(val: RunningProcesses) -> Self {
+		Message::Detection(DetectionMsg::Running(val.0))
+	}
+}
+
+impl From<FocusMsg> for Message {
+	fn from(val: FocusMsg) -> Self {
+		Message::Focus(val)
+	}
+}
+
+impl From<FocusedWindow> for Message {
+	fn from(val: FocusedWindow) -> Self {
+		Message::Focus(FocusMsg::Window(val))
+	}
+}
+
+impl From<NowPlaying> for Message {
+	fn from(val: NowPlaying) -> Self {
+		Message::NowPlaying(val)
+	}
+}
+
+impl From<IdleTime> for Message {
+	fn from(val: IdleTime) -> Self {
+		Message::Idle(val)
+	}
+}
+
+impl From<SettingsMsg> for Message {
+	fn from(val: SettingsMsg) -> Self {
+		Message::Settings(val)
+	}
+}
+
+impl From<ControlMessage> for Message {
+	fn from(val: ControlMessage) -> Self {
+		Message::Control(val)
+	}
+}
+
 impl From<TrayMessage> for Message {
 	fn from(val: TrayMessage) -> Self {
 		Message::TrayMessage(val)
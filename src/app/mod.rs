@@ -1,40 +1,124 @@
 pub mod message;
+pub mod preview;
 pub mod update;
 pub mod view;
 
-use std::process;
+use std::{process, sync::mpsc as std_mpsc, time::Duration};
 
+use chrono::{DateTime, Utc};
+use dark_light::Mode;
 use iced::{
-	Task,
+	Point, Size, Subscription, Task, Theme,
 	futures::{
 		SinkExt,
 		channel::mpsc::{self, UnboundedSender},
 	},
 	window,
 };
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use tokio::time;
 
 use crate::{
-	activity::Activity,
-	app::message::{MainThreadMessage, Message},
+	activity::{Activity, ImageSource, validate_image_url},
+	api::Api,
+	app::message::{
+		AssetMsg, CalendarMsg, ImageSlot, MainThreadMessage, Message, NotificationMsg, ScheduleMsg,
+	},
+	assets::{self, AssetLibrary, UrlThumbnails},
+	calendar::Calendar,
+	control::ControlMessage,
+	detection::{ProcessRules, RunningProcesses},
+	focus::{FocusedWindow, TitleRules},
+	gate::Gate,
+	idle::{Away, IdleTime},
+	mpris::{NowPlaying, Track},
+	notification::{Level, Notifications, TOAST_TIMEOUT},
+	pomodoro::Pomodoro,
 	presence::{Presence, PresenceThreadMessage},
-	settings::{Settings, SettingsFile},
-	tray::{Tray, TrayMessage},
+	preset::Preset,
+	rotation::Rotation,
+	schedule::Schedule,
+	settings::{self, Settings, SettingsFile, ThemeSetting},
+	tray::{Tray, TrayMessage, TrayUpdate},
 };
 
+/// How long typing has to pause before an image URL gets downloaded
+const THUMBNAIL_DELAY: Duration = Duration::from_millis(500);
+
 pub struct App {
 	send: UnboundedSender<MainThreadMessage>,
+	tray: std_mpsc::Sender<TrayUpdate>,
 	pub activity: Activity,
-	window_visible: bool,
+	pub presets: Vec<Preset>,
+	selected_preset: Option<usize>,
+	pub rotation: Rotation,
+	pub pomodoro: Pomodoro,
+	pub schedule: Schedule,
+	pub calendar: Calendar,
+	pub away: Away,
+	pub process_rules: ProcessRules,
+	pub title_rules: TitleRules,
+	now_playing: Option<Track>,
+	/// Last activity sent to discord, restored once the user is back
+	shown: Option<Activity>,
+	/// Roughly when the presence thread started, what `SinceStart` counts from
+	started: DateTime<Utc>,
+	main_window: Option<window::Id>,
+	settings_window: Option<window::Id>,
 	connection_state: ConnectionState,
 	show_date_picker: bool,
 	show_time_picker: bool,
+	show_end_date_picker: bool,
+	show_end_time_picker: bool,
 	pub settings: Settings,
+	assets: AssetLibrary,
+	/// Image the asset picker is open for
+	asset_picker: Option<ImageSlot>,
+	asset_search: String,
+	importing_assets: bool,
+	thumbnails: UrlThumbnails,
+	notifications: Notifications,
+	show_notifications: bool,
+	watchers: Watchers,
+}
+
+/// Pause switches of the background watchers, so they only poll while something uses them
+#[derive(Default)]
+struct Watchers {
+	processes: Gate,
+	focus: Gate,
+	mpris: Gate,
+	idle: Gate,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ConnectionState {
 	Disconnected,
 	Connecting,
 	Connected,
+	Reconnecting,
+}
+
+impl ConnectionState {
+	fn tray_label(&self) -> &'static str {
+		match self {
+			ConnectionState::Disconnected => "Connect",
+			ConnectionState::Connecting => "Connecting...",
+			ConnectionState::Connected => "Disconnect",
+			ConnectionState::Reconnecting => "Reconnecting, click to stop",
+		}
+	}
+}
+
+/// Valid image URLs of the slots set to a URL
+fn image_urls(activity: &Activity) -> impl Iterator<Item = &str> {
+	activity
+		.images()
+		.into_iter()
+		.filter(|(source, _)| *source == ImageSource::Url)
+		.filter_map(|(_, url)| url)
+		.filter(|url| validate_image_url(url).is_ok())
 }
 
 impl App {
@@ -42,26 +126,114 @@ impl App {
 		let (main_send, presence_recv) = mpsc::unbounded::<MainThreadMessage>();
 		let (presence_send, main_recv) = mpsc::unbounded::<PresenceThreadMessage>();
 		let (tray_send, tray_recv) = mpsc::unbounded::<TrayMessage>();
+		let (tray_update_send, tray_update_recv) = std_mpsc::channel::<TrayUpdate>();
+		let (control_send, control_recv) = mpsc::unbounded::<ControlMessage>();
+		let (process_send, process_recv) = mpsc::unbounded::<RunningProcesses>();
+		let (focus_send, focus_recv) = mpsc::unbounded::<FocusedWindow>();
+		let (mpris_send, mpris_recv) = mpsc::unbounded::<NowPlaying>();
+		let (idle_send, idle_recv) = mpsc::unbounded::<IdleTime>();
+		let watchers = Watchers::default();
 		Presence::spawn_thread(presence_send, presence_recv);
-		Tray::spawn_thread(tray_send);
+		Tray::spawn_thread(tray_send, tray_update_recv);
+		#[cfg(target_os = "linux")]
+		crate::detection::ProcessWatcher::spawn_thread(process_send, watchers.processes.clone());
+		#[cfg(target_os = "linux")]
+		crate::focus::FocusWatcher::spawn_thread(focus_send, watchers.focus.clone());
+		#[cfg(target_os = "linux")]
+		crate::mpris::MprisWatcher::spawn_thread(mpris_send, watchers.mpris.clone());
+		#[cfg(target_os = "linux")]
+		crate::idle::IdleWatcher::spawn_thread(idle_send, watchers.idle.clone());
+		#[cfg(not(target_os = "linux"))]
+		let _ = (process_send, focus_send, mpris_send, idle_send);
 
 		match SettingsFile::read() {
-			Ok(data) => (
-				App {
+			Ok(data) => {
+				if let Some(port) = data.settings.api_port {
+					Api::spawn_thread(port, control_send.clone());
+				}
+				#[cfg(unix)]
+				crate::control::Control::spawn_thread(control_send);
+
+				let mut app = App {
 					send: main_send,
+					tray: tray_update_send,
 					activity: data.activity,
-					window_visible: false,
+					presets: data.presets,
+					selected_preset: None,
+					rotation: data.rotation,
+					pomodoro: data.pomodoro,
+					schedule: data.schedule,
+					calendar: data.calendar,
+					away: data.away,
+					process_rules: data.process_rules,
+					title_rules: data.title_rules,
+					now_playing: None,
+					shown: None,
+					started: Utc::now(),
+					main_window: None,
+					settings_window: None,
 					connection_state: ConnectionState::Disconnected,
 					show_date_picker: false,
 					show_time_picker: false,
+					show_end_date_picker: false,
+					show_end_time_picker: false,
 					settings: data.settings,
-				},
-				Task::batch([
-					Task::stream(main_recv).map(|v| v.into()),
-					Task::stream(tray_recv).map(|v| v.into()),
-					Self::open_window(),
-				]),
-			),
+					assets: AssetLibrary::default(),
+					asset_picker: None,
+					asset_search: String::new(),
+					importing_assets: false,
+					thumbnails: UrlThumbnails::default(),
+					notifications: Notifications::default(),
+					show_notifications: false,
+					watchers,
+				};
+				// the entry could have been removed by hand
+				app.settings.autostart = settings::autostart_file().is_some_and(|v| v.exists());
+				app.sync_watchers();
+				let assets = app.load_assets();
+				let open = if app.settings.start_minimized {
+					Task::none()
+				} else {
+					app.open_window()
+				};
+				let connect = if app.settings.auto_connect {
+					Task::done(Message::AutoConnect)
+				} else {
+					Task::none()
+				};
+				let schedule = if app.schedule.enabled {
+					Task::done(ScheduleMsg::Refresh.into())
+				} else {
+					Task::none()
+				};
+				let calendar = if app.calendar.enabled {
+					Task::done(CalendarMsg::Reload.into())
+				} else {
+					Task::none()
+				};
+				let template_refresh = app.forward_presence_msg(
+					MainThreadMessage::TemplateRefresh(app.settings.template_refresh),
+				);
+
+				(
+					app,
+					Task::batch([
+						Task::stream(main_recv).map(|v| v.into()),
+						Task::stream(tray_recv).map(|v| v.into()),
+						Task::stream(control_recv).map(|v| v.into()),
+						Task::stream(process_recv).map(|v| v.into()),
+						Task::stream(focus_recv).map(|v| v.into()),
+						Task::stream(mpris_recv).map(|v| v.into()),
+						Task::stream(idle_recv).map(|v| v.into()),
+						template_refresh,
+						open,
+						connect,
+						schedule,
+						calendar,
+						assets,
+					]),
+				)
+			}
 			Err(err) => {
 				let err_string = err.to_string();
 
@@ -75,14 +247,95 @@ impl App {
 			}
 		}
 	}
-	fn open_window() -> Task<Message> {
-		let (_id, open) = window::open(window::Settings {
+	pub fn theme(&self, _window: window::Id) -> Theme {
+		match self.settings.theme {
+			ThemeSetting::Light => Theme::Light,
+			ThemeSetting::Dark => Theme::Dark,
+			ThemeSetting::System => match dark_light::detect() {
+				Ok(Mode::Light) => Theme::Light,
+				_ => Theme::Dark,
+			},
+		}
+	}
+	pub fn subscription(&self) -> Subscription<Message> {
+		let windows = window::events().map(|(id, event)| Message::Window(id, event));
+		// keeps the timer in the preview ticking
+		let preview = match self.main_window {
+			Some(_) => iced::time::every(Duration::from_secs(1)).map(|_| Message::Tick),
+			None => Subscription::none(),
+		};
+
+		Subscription::batch([windows, preview])
+	}
+	/// Opens the gates of the watchers something is listening to and closes the rest
+	fn sync_watchers(&self) {
+		let Watchers {
+			processes,
+			focus,
+			mpris,
+			idle,
+		} = &self.watchers;
+
+		processes.set(self.process_rules.enabled && !self.process_rules.rules.is_empty());
+		focus.set(self.title_rules.enabled && !self.title_rules.rules.is_empty());
+		mpris.set(self.settings.mpris);
+		idle.set(self.away.enabled);
+	}
+	fn open_window(&mut self) -> Task<Message> {
+		if let Some(id) = self.main_window {
+			return window::gain_focus(id);
+		}
+
+		let geometry = self
+			.settings
+			.window
+			.filter(|_| self.settings.remember_window);
+		let (id, open) = window::open(window::Settings {
+			size: geometry.map_or(window::Settings::default().size, |v| {
+				Size::new(v.width, v.height)
+			}),
+			position: match geometry.and_then(|v| v.x.zip(v.y)) {
+				Some((x, y)) => window::Position::Specific(Point::new(x, y)),
+				None => window::Position::default(),
+			},
+			..Default::default()
+		});
+		self.main_window = Some(id);
+
+		open.then(|_| Task::none())
+	}
+	fn open_settings_window(&mut self) -> Task<Message> {
+		if let Some(id) = self.settings_window {
+			return window::gain_focus(id);
+		}
+
+		let (id, open) = window::open(window::Settings {
+			size: Size::new(480., 460.),
 			..Default::default()
 		});
+		self.settings_window = Some(id);
 
 		open.then(|_| Task::none())
 	}
 	fn send_presence_msg(&mut self, msg: MainThreadMessage) -> Task<Message> {
+		match &msg {
+			MainThreadMessage::SetActivity(activity) => self.shown = Some(activity.clone()),
+			MainThreadMessage::ClearActivity | MainThreadMessage::Disconnect => self.shown = None,
+			_ => {}
+		}
+		// while away only remember what would have been shown
+		if self.away.active
+			&& matches!(
+				msg,
+				MainThreadMessage::SetActivity(_) | MainThreadMessage::ClearActivity
+			) {
+			return Task::none();
+		}
+
+		self.forward_presence_msg(msg)
+	}
+	/// Sends `msg` as is, without keeping track of what is shown
+	fn forward_presence_msg(&self, msg: MainThreadMessage) -> Task<Message> {
 		let mut sender = self.send.clone();
 		Task::future(async move { sender.send(msg).await }).then(|v| {
 			let Err(err) = v else {
@@ -92,6 +345,98 @@ impl App {
 			Task::done(Message::Error(err.to_string()))
 		})
 	}
+	fn activate(&mut self, activity: Activity) -> Task<Message> {
+		self.activity = activity;
+		let assets = self.load_assets();
+
+		let task = match self.connection_state {
+			ConnectionState::Disconnected => Task::done(Message::Connect),
+			ConnectionState::Connecting => Task::none(),
+			ConnectionState::Connected | ConnectionState::Reconnecting => {
+				Task::done(Message::SetActivity)
+			}
+		};
+		Task::batch([assets, task])
+	}
+	/// Reads the cached assets once the edited activity switched to another application,
+	/// and queues previews of image URLs that werent seen yet
+	fn load_assets(&mut self) -> Task<Message> {
+		let thumbnails = self.queue_thumbnails();
+		let app_id = self.activity.id.clone().filter(|v| !v.is_empty());
+		if app_id == self.assets.app_id {
+			return thumbnails;
+		}
+		self.assets = AssetLibrary {
+			app_id: app_id.clone(),
+			..Default::default()
+		};
+
+		let Some(app_id) = app_id else {
+			return thumbnails;
+		};
+		let assets = Task::future(async move {
+			let assets = assets::load(&app_id)
+				.await
+				.map_err(|err| format!("{err:#}"));
+			AssetMsg::Loaded(app_id, assets).into()
+		});
+		Task::batch([thumbnails, assets])
+	}
+	fn queue_thumbnails(&mut self) -> Task<Message> {
+		if !self.thumbnails.is_missing(image_urls(&self.activity)) {
+			return Task::none();
+		}
+
+		let generation = self.thumbnails.generation.restart();
+		Task::future(async move {
+			time::sleep(THUMBNAIL_DELAY).await;
+			AssetMsg::FetchThumbnails(generation).into()
+		})
+	}
+	/// Logs `text` and keeps it for the toasts and the history, mirrored to the desktop while
+	/// the window is closed
+	fn notify(&mut self, level: Level, text: String) -> Task<Message> {
+		match level {
+			Level::Info => info!("{text}"),
+			Level::Error => error!("{text}"),
+		}
+		let id = self.notifications.push(level, text.clone());
+		let expire = match level {
+			Level::Info => Task::future(async move {
+				time::sleep(TOAST_TIMEOUT).await;
+				NotificationMsg::Dismiss(id).into()
+			}),
+			Level::Error => Task::none(),
+		};
+		if !self.settings.desktop_notifications || self.main_window.is_some() {
+			return expire;
+		}
+
+		#[cfg(target_os = "linux")]
+		let desktop = Task::future(async move {
+			let summary = match level {
+				Level::Info => "Discord presence",
+				Level::Error => "Discord presence error",
+			};
+			if let Err(err) = crate::notification::send_desktop(summary, &text).await {
+				log::warn!("Failed to send desktop notification: {err:#}");
+			}
+		})
+		.then(|_| Task::none());
+		#[cfg(not(target_os = "linux"))]
+		let desktop = Task::none();
+
+		Task::batch([expire, desktop])
+	}
+	fn set_connection_state(&mut self, state: ConnectionState) {
+		self.send_tray_update(TrayUpdate::ConnectionLabel(state.tray_label()));
+		self.connection_state = state;
+	}
+	fn send_tray_update(&self, update: TrayUpdate) {
+		if let Err(err) = self.tray.send(update) {
+			error!("{err}");
+		}
+	}
 	fn write_settings(&self) -> Task<Message> {
 		let file = SettingsFile::from(self);
 
@@ -0,0 +1,193 @@
+use chrono::{DateTime, Local, TimeDelta, Timelike, Utc};
+use discord_rich_presence::activity::ActivityType;
+use iced::{
+	Element, Font, Length,
+	alignment::{Horizontal, Vertical},
+	font::Weight,
+	widget::{button, column, container, row, stack, text, tooltip},
+};
+
+use crate::{
+	activity::{Activity, TimestampType},
+	app::{App, message::Message},
+	template::{self, TemplateContext},
+};
+
+const CARD_WIDTH: f32 = 300.;
+const LARGE_IMAGE_SIZE: f32 = 80.;
+const SMALL_IMAGE_SIZE: f32 = 26.;
+
+impl App {
+	/// The edited activity roughly as a discord profile card shows it
+	pub fn preview(&self) -> Element<'_, Message, iced::Theme, iced::Renderer> {
+		let (activity, error) =
+			match template::render_activity(&self.activity, &TemplateContext::current()) {
+				Ok(activity) => (activity, None),
+				Err(err) => (self.activity.clone(), Some(err.to_string())),
+			};
+		let bold = Font {
+			weight: Weight::Bold,
+			..Font::DEFAULT
+		};
+
+		let state = match (&activity.state, activity.party_size, activity.party_max) {
+			(Some(state), Some(size), Some(max)) => Some(format!("{state} ({size} of {max})")),
+			(None, Some(size), Some(max)) => Some(format!("({size} of {max})")),
+			(state, _, _) => state.clone(),
+		};
+		let lines = [
+			// discord shows the name of the application here, only it knows that
+			Some(
+				activity
+					.id
+					.clone()
+					.map_or(String::from("No application id"), |id| {
+						format!("Application {id}")
+					}),
+			),
+			activity.details.clone(),
+			state,
+			timer(&activity, self.started, Utc::now()),
+		];
+		let info = lines
+			.into_iter()
+			.flatten()
+			.filter(|v| !v.is_empty())
+			.enumerate()
+			.fold(column![].spacing(2.), |info, (i, line)| {
+				let line = text(line).size(13);
+				info.push(if i == 0 { line.font(bold) } else { line })
+			});
+
+		let buttons = [
+			(&activity.button1_text, &activity.button1_url),
+			(&activity.button2_text, &activity.button2_url),
+		]
+		.into_iter()
+		.filter_map(|(label, url)| url.as_ref().and(label.clone()))
+		.fold(column![].spacing(5.), |buttons, label| {
+			buttons.push(
+				button(text(label).size(13).align_x(Horizontal::Center))
+					.style(button::secondary)
+					.width(Length::Fill),
+			)
+		});
+
+		let card = column![
+			text(header(&activity.activity_type)).size(12).font(bold),
+			row![images(&activity), info]
+				.spacing(10.)
+				.align_y(Vertical::Center),
+			buttons,
+		]
+		.spacing(10.);
+		let card = match error {
+			Some(err) => card.push(text(err).size(12).style(text::danger)),
+			None => card,
+		};
+
+		container(card)
+			.padding(12.)
+			.width(Length::Fixed(CARD_WIDTH))
+			.style(container::rounded_box)
+			.into()
+	}
+}
+
+fn header(activity_type: &ActivityType) -> &'static str {
+	match activity_type {
+		ActivityType::Playing => "Playing",
+		ActivityType::Listening => "Listening to",
+		ActivityType::Watching => "Watching",
+		ActivityType::Competing => "Competing in",
+	}
+}
+
+fn images(activity: &Activity) -> Element<'static, Message, iced::Theme, iced::Renderer> {
+	let large = activity.large_key.clone().filter(|v| !v.is_empty());
+	let small = activity.small_key.clone().filter(|v| !v.is_empty());
+	let Some(large) = large else {
+		return column![].into();
+	};
+
+	let large = placeholder(large, activity.large_text.clone(), LARGE_IMAGE_SIZE);
+	let small = small.map(|key| {
+		container(placeholder(
+			key,
+			activity.small_text.clone(),
+			SMALL_IMAGE_SIZE,
+		))
+		.width(Length::Fill)
+		.height(Length::Fill)
+		.align_x(Horizontal::Right)
+		.align_y(Vertical::Bottom)
+	});
+
+	match small {
+		Some(small) => stack![large, small]
+			.width(Length::Fixed(LARGE_IMAGE_SIZE + SMALL_IMAGE_SIZE / 3.))
+			.height(Length::Fixed(LARGE_IMAGE_SIZE + SMALL_IMAGE_SIZE / 3.))
+			.into(),
+		None => large,
+	}
+}
+
+/// Box standing in for an image, with its hover text as a tooltip
+fn placeholder(
+	key: String,
+	hover: Option<String>,
+	size: f32,
+) -> Element<'static, Message, iced::Theme, iced::Renderer> {
+	let image = container(text(key).size(12).align_x(Horizontal::Center))
+		.center(Length::Fixed(size))
+		.clip(true)
+		.style(container::bordered_box);
+
+	match hover.filter(|v| !v.is_empty()) {
+		Some(hover) => tooltip(
+			image,
+			container(text(hover).size(12))
+				.padding(5.)
+				.style(container::dark),
+			tooltip::Position::Top,
+		)
+		.into(),
+		None => image.into(),
+	}
+}
+
+/// `12:34 elapsed` or `01:02:03 left`, as discord counts from the timestamps that would be sent
+fn timer(activity: &Activity, started: DateTime<Utc>, now: DateTime<Utc>) -> Option<String> {
+	let (start, end) = match activity.timestamp_type {
+		TimestampType::SinceStart => (Some(started), None),
+		TimestampType::LocalTime => {
+			let local = now.with_timezone(&Local);
+			let midnight = TimeDelta::seconds(local.num_seconds_from_midnight().into());
+			(now.checked_sub_signed(midnight), None)
+		}
+		TimestampType::Custom => (activity.custom_timestamp, None),
+		// counts from whenever the activity gets set
+		TimestampType::SinceLastUpdate => (Some(now), None),
+		TimestampType::EndsAt => (activity.start_timestamp, Some(activity.end_timestamp?)),
+		TimestampType::Countdown => {
+			let countdown = TimeDelta::seconds(activity.countdown?.try_into().ok()?);
+			(Some(now), now.checked_add_signed(countdown))
+		}
+	};
+
+	match (start, end) {
+		(_, Some(end)) => Some(format!("{} left", clock(end - now))),
+		(Some(start), None) => Some(format!("{} elapsed", clock(now - start))),
+		(None, None) => None,
+	}
+}
+
+fn clock(delta: TimeDelta) -> String {
+	let seconds = delta.num_seconds().max(0);
+	let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+	match hours {
+		0 => format!("{minutes:02}:{seconds:02}"),
+		hours => format!("{hours:02}:{minutes:02}:{seconds:02}"),
+	}
+}
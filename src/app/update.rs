@@ -1,40 +1,73 @@
-use chrono::{Datelike, Timelike, Utc};
-use iced::{Task, futures::SinkExt, window};
-use iced_aw::time_picker::Time;
-use log::error;
+use chrono::{DateTime, Local, NaiveDate, Timelike, Utc};
+use iced::{Size, Task, futures::SinkExt, window};
+use iced_aw::{date_picker::Date, time_picker::Time};
+use log::warn;
+use tokio::time;
 use tray_icon::{MouseButton, MouseButtonState};
 
 use crate::{
 	activity::Activity,
 	app::{
-		App, ConnectionState,
-		message::{ActivityMsg, MainThreadMessage, Message},
+		App, ConnectionState, image_urls,
+		message::{
+			ActivityMsg, AssetMsg, AwayMsg, CalendarMsg, DetectionMsg, FocusMsg, ImageSlot,
+			MainThreadMessage, Message, NotificationMsg, PomodoroMsg, PresetMsg, RotationMsg,
+			ScheduleMsg, SettingsMsg,
+		},
 	},
+	assets, autostart, calendar,
+	control::{ControlMessage, ControlRequest, ControlResponse, patch_activity},
+	detection::ProcessRule,
+	focus::TitleRule,
+	idle::{AwayAction, IdleTime},
+	mpris::NowPlaying,
+	notification::Level,
+	pomodoro::PhasePlayback,
 	presence::PresenceThreadMessage,
-	tray::TrayMessage,
+	preset::Preset,
+	rotation::{Frame, FramePlayback},
+	rule_list,
+	schedule::ScheduleRule,
+	settings::{self, DEFAULT_API_PORT, WindowGeometry},
+	tray::{TrayMessage, TrayUpdate},
 };
 
 impl App {
 	pub fn update(&mut self, message: Message) -> Task<Message> {
+		let task = self.handle(message);
+		self.sync_watchers();
+
+		task
+	}
+	fn handle(&mut self, message: Message) -> Task<Message> {
 		match message {
 			Message::TrayMessage(TrayMessage::TrayIcon(tray_icon::TrayIconEvent::Click {
 				button: MouseButton::Left,
 				button_state: MouseButtonState::Down,
 				..
-			})) => {
-				self.window_visible = !self.window_visible;
-
-				if self.window_visible {
-					window::get_latest().and_then(window::close)
-				} else {
-					Self::open_window()
-				}
+			})) => match self.main_window {
+				Some(id) => window::close(id),
+				None => self.open_window(),
+			},
+			Message::TrayMessage(TrayMessage::Err(err)) => self.notify(Level::Error, err),
+			Message::TrayMessage(TrayMessage::Open) => self.open_window(),
+			Message::TrayMessage(TrayMessage::SetActivity(activity)) => self.activate(activity),
+			Message::TrayMessage(TrayMessage::ClearPresence) => {
+				self.send_presence_msg(MainThreadMessage::ClearActivity)
 			}
-			Message::TrayMessage(TrayMessage::Err(err)) => {
-				error!("{err}");
-				Task::none()
+			Message::TrayMessage(TrayMessage::ToggleRotation) => {
+				Task::done(RotationMsg::Toggle.into())
+			}
+			Message::TrayMessage(TrayMessage::TogglePomodoro) => {
+				Task::done(PomodoroMsg::Toggle.into())
 			}
-			Message::TrayMessage(TrayMessage::Open) => Self::open_window(),
+			Message::TrayMessage(TrayMessage::ToggleConnection) => match self.connection_state {
+				ConnectionState::Disconnected => Task::done(Message::Connect),
+				ConnectionState::Connecting => Task::none(),
+				ConnectionState::Connected | ConnectionState::Reconnecting => {
+					Task::done(Message::Disconnect)
+				}
+			},
 			Message::TrayMessage(TrayMessage::Exit) => {
 				let mut sender = self.send.clone();
 				Task::batch([
@@ -46,26 +79,57 @@ impl App {
 			Message::TrayMessage(_) => Task::none(),
 			Message::Presence(PresenceThreadMessage::Err(err)) => {
 				if matches!(self.connection_state, ConnectionState::Connecting) {
-					self.connection_state = ConnectionState::Disconnected;
+					self.set_connection_state(ConnectionState::Disconnected);
 				}
-				error!("{err}");
-				Task::none()
+				self.notify(Level::Error, err)
 			}
 			Message::Presence(PresenceThreadMessage::Connected) => {
-				self.connection_state = ConnectionState::Connected;
+				self.set_connection_state(ConnectionState::Connected);
+
+				let notify = self.notify(Level::Info, String::from("Connected to discord"));
+				let restore = self.restore_presence();
+				let restore = if self.away.active {
+					restore.chain(self.show_away())
+				} else {
+					restore
+				};
+				Task::batch([notify, restore])
+			}
+			Message::Presence(PresenceThreadMessage::Reconnected) => {
+				self.set_connection_state(ConnectionState::Connected);
+
+				// restoring would replace what was up before, like the focus timer or a track
+				self.notify(Level::Info, String::from("Reconnected to discord"))
+			}
+			Message::Presence(PresenceThreadMessage::ConnectionLost) => {
+				self.set_connection_state(ConnectionState::Disconnected);
+
+				self.notify(Level::Error, String::from("Lost connection to discord"))
+			}
+			Message::Presence(PresenceThreadMessage::Reconnecting) => {
+				self.set_connection_state(ConnectionState::Reconnecting);
 
 				Task::none()
 			}
 			Message::Presence(PresenceThreadMessage::Disconnected) => {
-				self.connection_state = ConnectionState::Disconnected;
+				self.set_connection_state(ConnectionState::Disconnected);
 
 				Task::none()
 			}
 			Message::Exit => Task::none(),
-			Message::Error(err) => {
-				error!("{err}");
-				Task::none()
+			Message::OpenSettings => self.open_settings_window(),
+			Message::NewActivity => {
+				self.activity = Activity {
+					timestamp_type: self.settings.default_timestamp,
+					..Default::default()
+				};
+				self.selected_preset = None;
+
+				self.load_assets()
 			}
+			Message::Window(id, event) => self.update_window(id, event),
+			Message::Settings(msg) => self.update_settings(msg),
+			Message::Error(err) => self.notify(Level::Error, err),
 			Message::SetActivity => {
 				let activity = self.activity.clone();
 
@@ -78,11 +142,21 @@ impl App {
 					return Task::done(Message::Error(String::from("No id")));
 				};
 				let id = id.clone();
-				self.connection_state = ConnectionState::Connecting;
+				self.set_connection_state(ConnectionState::Connecting);
 				self.send_presence_msg(MainThreadMessage::Connect(id))
 					.chain(self.send_presence_msg(MainThreadMessage::SetActivity(activity)))
 					.chain(self.write_settings())
 			}
+			Message::AutoConnect => {
+				let activity = self.activity.clone();
+				let Some(id) = activity.id.clone() else {
+					warn!("Auto connect is enabled but the saved activity has no id");
+					return Task::none();
+				};
+				self.set_connection_state(ConnectionState::Connecting);
+				self.send_presence_msg(MainThreadMessage::WaitForDiscord(id))
+					.chain(self.send_presence_msg(MainThreadMessage::SetActivity(activity)))
+			}
 			Message::Disconnect => self.send_presence_msg(MainThreadMessage::Disconnect),
 			Message::Activity(msg) => {
 				match msg {
@@ -120,31 +194,41 @@ impl App {
 						let _ = self.activity.party_max.insert(size);
 					}
 					ActivityMsg::CustomDate(date) => {
-						let timestamp = self.activity.custom_timestamp.unwrap_or(Utc::now());
-						let timestamp = timestamp.with_year(date.year).unwrap();
-						let timestamp = timestamp.with_month(date.month).unwrap();
-						let timestamp = timestamp.with_day(date.day).unwrap();
+						let timestamp = match with_date(self.activity.custom_timestamp, date) {
+							Ok(timestamp) => timestamp,
+							Err(err) => return self.notify(Level::Error, err),
+						};
 						let _ = self.activity.custom_timestamp.insert(timestamp);
 						self.show_date_picker = false;
 					}
 					ActivityMsg::CustomTime(time) => {
-						let timestamp = self.activity.custom_timestamp.unwrap_or(Utc::now());
-						let Time::Hms {
-							hour,
-							minute,
-							second,
-							period: _,
-						} = time
+						let Some(timestamp) = with_time(self.activity.custom_timestamp, time)
 						else {
 							return Task::none();
 						};
-						let timestamp = timestamp.with_hour(hour).unwrap();
-						let timestamp = timestamp.with_minute(minute).unwrap();
-						let timestamp = timestamp.with_second(second).unwrap();
 
 						let _ = self.activity.custom_timestamp.insert(timestamp);
 						self.show_time_picker = false;
 					}
+					ActivityMsg::EndDate(date) => {
+						let timestamp = match with_date(self.activity.end_timestamp, date) {
+							Ok(timestamp) => timestamp,
+							Err(err) => return self.notify(Level::Error, err),
+						};
+						let _ = self.activity.end_timestamp.insert(timestamp);
+						self.show_end_date_picker = false;
+					}
+					ActivityMsg::EndTime(time) => {
+						let Some(timestamp) = with_time(self.activity.end_timestamp, time) else {
+							return Task::none();
+						};
+
+						let _ = self.activity.end_timestamp.insert(timestamp);
+						self.show_end_time_picker = false;
+					}
+					ActivityMsg::Countdown(minutes) => {
+						self.activity.countdown = (minutes > 0).then_some(minutes * 60);
+					}
 					ActivityMsg::Button1Text(v) => {
 						let _ = self.activity.button1_text.insert(v);
 					}
@@ -163,15 +247,27 @@ impl App {
 					ActivityMsg::SmallImageKey(v) => {
 						let _ = self.activity.small_key.insert(v);
 					}
+					ActivityMsg::SmallImageSource(v) => {
+						self.activity.small_source = v;
+						if self.asset_picker == Some(ImageSlot::Small) {
+							self.asset_picker = None;
+						}
+					}
 					ActivityMsg::LargeImageText(v) => {
 						let _ = self.activity.large_text.insert(v);
 					}
 					ActivityMsg::LargeImageKey(v) => {
 						let _ = self.activity.large_key.insert(v);
 					}
+					ActivityMsg::LargeImageSource(v) => {
+						self.activity.large_source = v;
+						if self.asset_picker == Some(ImageSlot::Large) {
+							self.asset_picker = None;
+						}
+					}
 				};
 
-				Task::none()
+				self.load_assets()
 			}
 			Message::ChooseDate => {
 				self.show_date_picker = !self.show_date_picker;
@@ -191,7 +287,25 @@ impl App {
 				self.show_time_picker = false;
 				Task::none()
 			}
-			Message::None => Task::none(),
+			Message::ChooseEndDate => {
+				self.show_end_date_picker = !self.show_end_date_picker;
+
+				Task::none()
+			}
+			Message::ChooseEndTime => {
+				self.show_end_time_picker = !self.show_end_time_picker;
+
+				Task::none()
+			}
+			Message::CancelEndDate => {
+				self.show_end_date_picker = false;
+				Task::none()
+			}
+			Message::CancelEndTime => {
+				self.show_end_time_picker = false;
+				Task::none()
+			}
+			Message::None | Message::Tick => Task::none(),
 			Message::OpenActivity => Task::future(async {
 				let fd = rfd::AsyncFileDialog::new()
 					.add_filter("activity (.crp)", &["crp"])
@@ -202,15 +316,8 @@ impl App {
 					return Message::None;
 				};
 
-				let res = tokio::fs::read(fd.path()).await;
-				match res {
-					Ok(data) => {
-						let activity = serde_xml_rs::from_reader::<Activity, _>(data.as_slice());
-						match activity {
-							Ok(v) => Message::LoadActivity(v),
-							Err(err) => Message::Error(err.to_string()),
-						}
-					}
+				match Activity::read(fd.path()).await {
+					Ok(v) => Message::LoadActivity(v),
 					Err(err) => Message::Error(err.to_string()),
 				}
 			}),
@@ -237,8 +344,970 @@ impl App {
 			Message::LoadActivity(activity) => {
 				self.activity = activity;
 
+				self.load_assets()
+			}
+			Message::Control(ControlMessage { request, reply }) => {
+				let task = match request {
+					ControlRequest::Set { activity } => self.activate(activity),
+					ControlRequest::Patch { activity } => {
+						match patch_activity(&self.activity, &activity) {
+							Ok(activity) => self.activate(activity),
+							Err(err) => {
+								let _ = reply.unbounded_send(ControlResponse::error(err));
+								return Task::none();
+							}
+						}
+					}
+					ControlRequest::Clear => {
+						if !matches!(self.connection_state, ConnectionState::Connected) {
+							let _ = reply.unbounded_send(ControlResponse::error(
+								"Not connected to discord, nothing to clear",
+							));
+							return Task::none();
+						}
+
+						self.send_presence_msg(MainThreadMessage::ClearActivity)
+					}
+					ControlRequest::Connect => Task::done(Message::Connect),
+					ControlRequest::Disconnect => Task::done(Message::Disconnect),
+					ControlRequest::Get => {
+						let _ = reply.unbounded_send(ControlResponse::state(
+							&self.activity,
+							&self.connection_state,
+						));
+						return Task::none();
+					}
+				};
+				let _ = reply.unbounded_send(ControlResponse::accepted(&self.activity));
+
+				task
+			}
+			Message::Preset(msg) => self.update_preset(msg),
+			Message::Rotation(msg) => self.update_rotation(msg),
+			Message::Pomodoro(msg) => self.update_pomodoro(msg),
+			Message::Schedule(msg) => self.update_schedule(msg),
+			Message::Calendar(msg) => self.update_calendar(msg),
+			Message::Detection(msg) => self.update_detection(msg),
+			Message::Focus(msg) => self.update_focus(msg),
+			Message::Away(msg) => self.update_away(msg),
+			Message::Assets(msg) => self.update_assets(msg),
+			Message::Notification(msg) => self.update_notification(msg),
+			Message::Idle(IdleTime(idle)) => {
+				let away = self.away.is_idle(idle);
+				if away == self.away.active {
+					return Task::none();
+				}
+
+				self.set_away(away)
+			}
+			Message::NowPlaying(NowPlaying(track)) => {
+				let was_playing = self.now_playing.is_some();
+				self.now_playing = track;
+				if !self.settings.mpris {
+					return Task::none();
+				}
+
+				self.apply_now_playing(was_playing)
+			}
+		}
+	}
+	/// Sends whatever automation should be showing after a fresh connection
+	fn restore_presence(&mut self) -> Task<Message> {
+		if let Some(event) = &self.calendar.current {
+			let activity = self.calendar.activity(event, &self.activity);
+			return self.send_presence_msg(MainThreadMessage::SetActivity(activity));
+		}
+		match (self.process_rules.active, self.schedule.active) {
+			(Some(_), _) => self.apply_process_rules(true),
+			(None, Some(i)) => match self.schedule.rules.get(i) {
+				Some(rule) => {
+					self.send_presence_msg(MainThreadMessage::SetActivity(rule.activity.clone()))
+				}
+				None => Task::none(),
+			},
+			(None, None) => Task::none(),
+		}
+	}
+	/// Hands back to the automations, showing `fallback` when none of them is active
+	fn restore_presence_or(&mut self, fallback: Activity) -> Task<Message> {
+		if !self.automation_active() {
+			return self.send_presence_msg(MainThreadMessage::SetActivity(fallback));
+		}
+
+		self.restore_presence()
+	}
+	fn automation_active(&self) -> bool {
+		self.calendar.current.is_some()
+			|| self.process_rules.active.is_some()
+			|| self.schedule.active.is_some()
+	}
+	fn update_assets(&mut self, msg: AssetMsg) -> Task<Message> {
+		match msg {
+			AssetMsg::Loaded(app_id, assets) | AssetMsg::Imported(app_id, assets) => {
+				if self.assets.app_id.as_ref() == Some(&app_id) {
+					self.importing_assets = false;
+					match assets {
+						Ok(assets) => {
+							self.assets.assets = assets;
+							self.assets.error = None;
+						}
+						Err(err) => self.assets.error = Some(err),
+					}
+				}
+
+				Task::none()
+			}
+			AssetMsg::Import => {
+				let Some(app_id) = self.assets.app_id.clone() else {
+					return Task::none();
+				};
+				self.importing_assets = true;
+
+				Task::future(async move {
+					let fd = rfd::AsyncFileDialog::new()
+						.add_filter("asset list (.json)", &["json"])
+						.pick_file()
+						.await;
+					let assets = match fd {
+						Some(fd) => assets::import(&app_id, fd.path()).await,
+						// keeps whatever was cached before
+						None => assets::load(&app_id).await,
+					};
+
+					AssetMsg::Imported(app_id, assets.map_err(|err| format!("{err:#}"))).into()
+				})
+			}
+			AssetMsg::Toggle(slot) => {
+				self.asset_picker = match self.asset_picker {
+					Some(open) if open == slot => None,
+					_ => Some(slot),
+				};
+				self.asset_search.clear();
+
+				Task::none()
+			}
+			AssetMsg::Search(query) => {
+				self.asset_search = query;
+
+				Task::none()
+			}
+			AssetMsg::Pick(key) => {
+				let Some(slot) = self.asset_picker.take() else {
+					return Task::none();
+				};
+				self.asset_search.clear();
+
+				Task::done(match slot {
+					ImageSlot::Large => ActivityMsg::LargeImageKey(key).into(),
+					ImageSlot::Small => ActivityMsg::SmallImageKey(key).into(),
+				})
+			}
+			AssetMsg::FetchThumbnails(generation) => {
+				if !self.thumbnails.generation.is_current(generation) {
+					return Task::none();
+				}
+
+				let urls = self.thumbnails.request(image_urls(&self.activity));
+				Task::batch(urls.into_iter().map(|url| {
+					Task::future(async move {
+						let path = match assets::cache_url(&url).await {
+							Ok(path) => Some(path),
+							Err(err) => {
+								warn!("Failed to download {url}: {err:#}");
+								None
+							}
+						};
+
+						AssetMsg::Thumbnail(url, path).into()
+					})
+				}))
+			}
+			AssetMsg::Thumbnail(url, path) => {
+				self.thumbnails.insert(url, path);
+
 				Task::none()
 			}
 		}
 	}
+	fn update_notification(&mut self, msg: NotificationMsg) -> Task<Message> {
+		match msg {
+			NotificationMsg::Dismiss(id) => self.notifications.dismiss(id),
+			NotificationMsg::DismissAll => self.notifications.dismiss_all(),
+			NotificationMsg::Clear => self.notifications.history.clear(),
+			NotificationMsg::TogglePanel => self.show_notifications = !self.show_notifications,
+		}
+
+		Task::none()
+	}
+	fn update_away(&mut self, msg: AwayMsg) -> Task<Message> {
+		match msg {
+			AwayMsg::Enabled(enabled) => self.away.enabled = enabled,
+			AwayMsg::TimeoutMinutes(minutes) => self.away.timeout_minutes = minutes,
+			AwayMsg::Action(action) => self.away.action = action,
+			AwayMsg::UseCurrent => self.away.activity = Some(self.activity.clone()),
+			AwayMsg::Reset => self.away.activity = None,
+		}
+
+		let update = match (self.away.enabled, self.away.active) {
+			(false, true) => self.set_away(false),
+			// show the changed away activity right away
+			(true, true) => self.set_away(true),
+			(_, false) => Task::none(),
+		};
+		update.chain(self.write_settings())
+	}
+	fn set_away(&mut self, away: bool) -> Task<Message> {
+		self.away.active = away;
+		if !matches!(self.connection_state, ConnectionState::Connected) {
+			return Task::none();
+		}
+
+		match (away, self.shown.clone()) {
+			(true, _) => self.show_away(),
+			(false, Some(activity)) => {
+				self.forward_presence_msg(MainThreadMessage::SetActivity(activity))
+			}
+			(false, None) => self.forward_presence_msg(MainThreadMessage::ClearActivity),
+		}
+	}
+	fn show_away(&self) -> Task<Message> {
+		match self.away.action {
+			AwayAction::Activity => self.forward_presence_msg(MainThreadMessage::SetActivity(
+				self.away.activity(&self.activity),
+			)),
+			AwayAction::Clear => self.forward_presence_msg(MainThreadMessage::ClearActivity),
+		}
+	}
+	fn update_window(&mut self, id: window::Id, event: window::Event) -> Task<Message> {
+		if self.settings_window == Some(id) {
+			if let window::Event::Closed = event {
+				self.settings_window = None;
+			}
+			return Task::none();
+		}
+		if self.main_window != Some(id) {
+			return Task::none();
+		}
+
+		match event {
+			window::Event::Closed => {
+				self.main_window = None;
+
+				self.write_settings()
+			}
+			window::Event::Resized(Size { width, height }) if self.settings.remember_window => {
+				let geometry = self.settings.window.get_or_insert(WindowGeometry {
+					width,
+					height,
+					x: None,
+					y: None,
+				});
+				geometry.width = width;
+				geometry.height = height;
+
+				Task::none()
+			}
+			window::Event::Moved(position) if self.settings.remember_window => {
+				if let Some(geometry) = &mut self.settings.window {
+					geometry.x = Some(position.x);
+					geometry.y = Some(position.y);
+				}
+
+				Task::none()
+			}
+			_ => Task::none(),
+		}
+	}
+	fn update_settings(&mut self, msg: SettingsMsg) -> Task<Message> {
+		if let SettingsMsg::Autostart(enabled) = msg {
+			let Some(file) = settings::autostart_file() else {
+				return Task::none();
+			};
+
+			return Task::future(async move {
+				if enabled {
+					autostart::enable(&file).await
+				} else {
+					autostart::disable(&file).await
+				}
+			})
+			.then(move |v| match v {
+				Ok(()) => Task::done(SettingsMsg::AutostartSet(enabled).into()),
+				Err(err) => Task::done(Message::Error(err.to_string())),
+			});
+		}
+		if let SettingsMsg::TemplateRefresh(enabled) = msg {
+			self.settings.template_refresh = enabled;
+
+			return self
+				.forward_presence_msg(MainThreadMessage::TemplateRefresh(enabled))
+				.chain(self.write_settings());
+		}
+		if let SettingsMsg::Mpris(enabled) = msg {
+			self.settings.mpris = enabled;
+			let task = match (enabled, &self.now_playing) {
+				(true, _) => self.apply_now_playing(false),
+				// hand the presence back to the edited activity
+				(false, Some(_)) if matches!(self.connection_state, ConnectionState::Connected) => {
+					self.send_presence_msg(MainThreadMessage::SetActivity(self.activity.clone()))
+				}
+				(false, _) => Task::none(),
+			};
+
+			return task.chain(self.write_settings());
+		}
+
+		match msg {
+			SettingsMsg::AutoConnect(v) => self.settings.auto_connect = v,
+			SettingsMsg::StartMinimized(v) => self.settings.start_minimized = v,
+			SettingsMsg::RememberWindow(v) => {
+				self.settings.remember_window = v;
+				if !v {
+					self.settings.window = None;
+				}
+			}
+			SettingsMsg::DefaultTimestamp(v) => self.settings.default_timestamp = v,
+			SettingsMsg::Theme(v) => self.settings.theme = v,
+			SettingsMsg::LogLevel(v) => self.settings.log_level = v,
+			SettingsMsg::DesktopNotifications(v) => self.settings.desktop_notifications = v,
+			SettingsMsg::ApiEnabled(v) => {
+				self.settings.api_port = v.then_some(DEFAULT_API_PORT);
+			}
+			SettingsMsg::ApiPort(v) => {
+				if self.settings.api_port.is_some() {
+					self.settings.api_port = Some(v);
+				}
+			}
+			SettingsMsg::AutostartSet(v) => self.settings.autostart = v,
+			SettingsMsg::Autostart(_) | SettingsMsg::Mpris(_) | SettingsMsg::TemplateRefresh(_) => {
+			}
+		}
+
+		self.write_settings()
+	}
+	fn update_preset(&mut self, msg: PresetMsg) -> Task<Message> {
+		match msg {
+			PresetMsg::Select(i) => {
+				let Some(preset) = self.presets.get(i) else {
+					return Task::none();
+				};
+				self.activity = preset.activity.clone();
+				self.selected_preset = Some(i);
+
+				self.load_assets()
+			}
+			PresetMsg::Apply(i) => {
+				let Some(preset) = self.presets.get(i) else {
+					return Task::none();
+				};
+				self.activity = preset.activity.clone();
+				self.selected_preset = Some(i);
+				let activity = self.activity.clone();
+
+				Task::batch([
+					self.load_assets(),
+					self.send_presence_msg(MainThreadMessage::SetActivity(activity))
+						.chain(self.write_settings()),
+				])
+			}
+			PresetMsg::Rename(name) => {
+				let Some(preset) = self.selected_preset.and_then(|i| self.presets.get_mut(i))
+				else {
+					return Task::none();
+				};
+				preset.name = name;
+
+				self.write_settings()
+			}
+			PresetMsg::New => {
+				let name = format!("Preset {}", self.presets.len() + 1);
+				self.presets.push(Preset::new(name, self.activity.clone()));
+				self.selected_preset = Some(self.presets.len() - 1);
+
+				self.write_settings()
+			}
+			PresetMsg::Duplicate => {
+				let Some(i) = self.selected_preset else {
+					return Task::none();
+				};
+				let Some(preset) = self.presets.get(i) else {
+					return Task::none();
+				};
+				let copy = Preset::new(format!("{} copy", preset.name), preset.activity.clone());
+				self.presets.insert(i + 1, copy);
+				self.selected_preset = Some(i + 1);
+
+				self.write_settings()
+			}
+			PresetMsg::Delete => {
+				let Some(i) = self.selected_preset.take() else {
+					return Task::none();
+				};
+				rule_list::remove(&mut self.presets, i);
+
+				self.write_settings()
+			}
+			PresetMsg::Save => {
+				let Some(preset) = self.selected_preset.and_then(|i| self.presets.get_mut(i))
+				else {
+					return Task::none();
+				};
+				preset.activity = self.activity.clone();
+
+				self.write_settings()
+			}
+		}
+	}
+	fn update_rotation(&mut self, msg: RotationMsg) -> Task<Message> {
+		match msg {
+			RotationMsg::Start => {
+				let Some(frame) = self.rotation.start() else {
+					return Task::done(Message::Error(String::from("Rotation has no frames")));
+				};
+				self.send_tray_update(TrayUpdate::RotationLabel("Stop rotation"));
+
+				self.play_frame(frame)
+			}
+			RotationMsg::Stop => {
+				self.rotation.stop();
+				self.send_tray_update(TrayUpdate::RotationLabel("Start rotation"));
+
+				Task::none()
+			}
+			RotationMsg::Toggle => {
+				if self.rotation.is_running() {
+					Task::done(RotationMsg::Stop.into())
+				} else {
+					Task::done(RotationMsg::Start.into())
+				}
+			}
+			RotationMsg::Next(generation) => match self.rotation.advance(generation) {
+				Some(frame) => self.play_frame(frame),
+				None => Task::none(),
+			},
+			RotationMsg::AddFrame => {
+				self.rotation.frames.push(Frame::new(self.activity.clone()));
+
+				self.write_settings()
+			}
+			RotationMsg::RemoveFrame(i) => {
+				self.rotation.remove(i);
+				if !self.rotation.is_running() {
+					self.send_tray_update(TrayUpdate::RotationLabel("Start rotation"));
+				}
+
+				self.write_settings()
+			}
+			RotationMsg::MoveUp(i) => {
+				rule_list::move_up(&mut self.rotation.frames, i);
+
+				self.write_settings()
+			}
+			RotationMsg::Duration(i, duration) => {
+				let Some(frame) = self.rotation.frames.get_mut(i) else {
+					return Task::none();
+				};
+				frame.duration = duration;
+
+				self.write_settings()
+			}
+		}
+	}
+	fn update_schedule(&mut self, msg: ScheduleMsg) -> Task<Message> {
+		match msg {
+			ScheduleMsg::Enabled(enabled) => {
+				self.schedule.enabled = enabled;
+
+				return self.apply_schedule().chain(self.write_settings());
+			}
+			ScheduleMsg::Refresh => return self.apply_schedule(),
+			ScheduleMsg::Tick(generation) => {
+				if !self.schedule.is_current(generation) {
+					return Task::none();
+				}
+
+				return self.apply_schedule();
+			}
+			ScheduleMsg::AddRule => {
+				self.schedule
+					.rules
+					.push(ScheduleRule::new(self.activity.clone()));
+			}
+			ScheduleMsg::RemoveRule(i) => {
+				rule_list::remove(&mut self.schedule.rules, i);
+				// the indices shifted, so whatever is active has to be sent again
+				self.schedule.active = None;
+			}
+			ScheduleMsg::MoveUp(i) => {
+				rule_list::move_up(&mut self.schedule.rules, i);
+				self.schedule.active = None;
+			}
+			ScheduleMsg::ToggleDay(i, day) => {
+				if let Some(rule) = self.schedule.rules.get_mut(i) {
+					rule.toggle_day(day);
+				}
+			}
+			ScheduleMsg::Start(i, time) => {
+				if let Some(rule) = self.schedule.rules.get_mut(i) {
+					rule.start = time;
+				}
+			}
+			ScheduleMsg::End(i, time) => {
+				if let Some(rule) = self.schedule.rules.get_mut(i) {
+					rule.end = time;
+				}
+			}
+		}
+
+		self.apply_schedule().chain(self.write_settings())
+	}
+	/// Sends the activity of the rule active right now and arms a timer for the next boundary
+	fn apply_schedule(&mut self) -> Task<Message> {
+		let generation = self.schedule.generation.restart();
+		if !self.schedule.enabled {
+			let previous = self.schedule.active.take();
+			return match previous {
+				Some(_) if matches!(self.connection_state, ConnectionState::Connected) => {
+					self.send_presence_msg(MainThreadMessage::SetActivity(self.activity.clone()))
+				}
+				_ => Task::none(),
+			};
+		}
+
+		let wait = self.schedule.wait();
+		let timer = Task::future(async move {
+			time::sleep(wait).await;
+			Message::from(ScheduleMsg::Tick(generation))
+		});
+
+		let active = self.schedule.active_at(Local::now().naive_local());
+		if active == self.schedule.active {
+			return timer;
+		}
+		self.schedule.active = active;
+		// a running process rule outranks the schedule
+		if !matches!(self.connection_state, ConnectionState::Connected)
+			|| self.process_rules.active.is_some()
+		{
+			return timer;
+		}
+
+		let activity = match active.and_then(|i| self.schedule.rules.get(i)) {
+			Some(rule) => rule.activity.clone(),
+			None => self.activity.clone(),
+		};
+		Task::batch([
+			self.send_presence_msg(MainThreadMessage::SetActivity(activity)),
+			timer,
+		])
+	}
+	fn update_calendar(&mut self, msg: CalendarMsg) -> Task<Message> {
+		match msg {
+			CalendarMsg::Enabled(enabled) => {
+				self.calendar.enabled = enabled;
+
+				Task::done(CalendarMsg::Reload.into()).chain(self.write_settings())
+			}
+			CalendarMsg::Path(path) => {
+				self.calendar.path = path;
+
+				self.write_settings()
+			}
+			CalendarMsg::Browse => Task::future(async {
+				let fd = rfd::AsyncFileDialog::new()
+					.add_filter("calendar (.ics)", &["ics"])
+					.pick_file()
+					.await;
+
+				match fd {
+					Some(fd) => CalendarMsg::Picked(fd.path().display().to_string()).into(),
+					None => Message::None,
+				}
+			}),
+			CalendarMsg::Picked(path) => {
+				self.calendar.path = path;
+
+				Task::done(CalendarMsg::Reload.into()).chain(self.write_settings())
+			}
+			CalendarMsg::Redact(redact) => {
+				self.calendar.redact = redact;
+
+				self.apply_calendar(true).chain(self.write_settings())
+			}
+			CalendarMsg::ImageKey(key) => {
+				self.calendar.image_key = key;
+
+				self.apply_calendar(true).chain(self.write_settings())
+			}
+			CalendarMsg::Reload => {
+				let generation = self.calendar.generation.restart();
+				if !self.calendar.enabled {
+					self.calendar.events.clear();
+					self.calendar.error = None;
+					return self.apply_calendar(false);
+				}
+
+				let path = self.calendar.path.clone();
+				Task::future(async move {
+					let events = calendar::load(path).await.map_err(|err| format!("{err:#}"));
+					CalendarMsg::Loaded(generation, events).into()
+				})
+			}
+			CalendarMsg::Loaded(generation, events) => {
+				if !self.calendar.is_current(generation) {
+					return Task::none();
+				}
+				match events {
+					Ok(events) => {
+						self.calendar.events = events;
+						self.calendar.error = None;
+					}
+					Err(err) => {
+						warn!("Failed to load calendar: {err}");
+						self.calendar.events.clear();
+						self.calendar.error = Some(err);
+					}
+				}
+
+				self.apply_calendar(false)
+			}
+			CalendarMsg::Tick(generation) => {
+				if !self.calendar.is_current(generation) {
+					return Task::none();
+				}
+
+				Task::done(CalendarMsg::Reload.into())
+			}
+		}
+	}
+	/// Sends the event in progress or hands back once it ended, and arms the next reload
+	fn apply_calendar(&mut self, force: bool) -> Task<Message> {
+		let generation = self.calendar.generation.restart();
+		let current = self.calendar.current_at(Utc::now()).cloned();
+		let changed = current != self.calendar.current;
+		self.calendar.current = current;
+
+		let update = match (&self.calendar.current, &self.connection_state) {
+			(_, ConnectionState::Connected) if !changed && !force => Task::none(),
+			(Some(event), ConnectionState::Connected) => {
+				let activity = self.calendar.activity(event, &self.activity);
+				self.send_presence_msg(MainThreadMessage::SetActivity(activity))
+			}
+			// only revert when the calendar was showing something
+			(None, ConnectionState::Connected) if changed => {
+				self.restore_presence_or(self.activity.clone())
+			}
+			_ => Task::none(),
+		};
+		if !self.calendar.enabled {
+			return update;
+		}
+
+		let wait = self.calendar.wait();
+		let timer = Task::future(async move {
+			time::sleep(wait).await;
+			Message::from(CalendarMsg::Tick(generation))
+		});
+		Task::batch([update, timer])
+	}
+	fn update_detection(&mut self, msg: DetectionMsg) -> Task<Message> {
+		match msg {
+			DetectionMsg::Running(running) => {
+				self.process_rules.running = running;
+
+				self.apply_process_rules(false)
+			}
+			DetectionMsg::Enabled(v) => {
+				self.process_rules.enabled = v;
+
+				self.apply_process_rules(false).chain(self.write_settings())
+			}
+			DetectionMsg::AddRule => {
+				self.process_rules
+					.rules
+					.push(ProcessRule::new(self.activity.clone()));
+
+				self.write_settings()
+			}
+			DetectionMsg::RemoveRule(i) => {
+				rule_list::remove(&mut self.process_rules.rules, i);
+
+				self.apply_process_rules(true).chain(self.write_settings())
+			}
+			DetectionMsg::MoveUp(i) => {
+				rule_list::move_up(&mut self.process_rules.rules, i);
+
+				self.apply_process_rules(true).chain(self.write_settings())
+			}
+			DetectionMsg::Processes(i, processes) => {
+				let Some(rule) = self.process_rules.rules.get_mut(i) else {
+					return Task::none();
+				};
+				rule.processes = processes;
+
+				self.apply_process_rules(false).chain(self.write_settings())
+			}
+		}
+	}
+	/// Sends the activity of the first matching rule, or the edited activity once nothing matches anymore
+	fn apply_process_rules(&mut self, force: bool) -> Task<Message> {
+		let found = self.process_rules.find();
+		if !force && found == self.process_rules.active {
+			return Task::none();
+		}
+		let previous = std::mem::replace(&mut self.process_rules.active, found);
+		if found.is_none() && previous.is_none() {
+			return Task::none();
+		}
+		if !matches!(self.connection_state, ConnectionState::Connected) {
+			return Task::none();
+		}
+
+		match found {
+			Some(i) => {
+				let activity = self.process_rules.rules[i].activity.clone();
+				self.send_presence_msg(MainThreadMessage::SetActivity(activity))
+			}
+			None => self.restore_presence_or(self.activity.clone()),
+		}
+	}
+	fn update_focus(&mut self, msg: FocusMsg) -> Task<Message> {
+		match msg {
+			FocusMsg::Window(window) => {
+				self.title_rules.window = Some(window);
+
+				return self.apply_title_rules();
+			}
+			FocusMsg::Enabled(v) => {
+				self.title_rules.enabled = v;
+
+				return self.apply_title_rules().chain(self.write_settings());
+			}
+			FocusMsg::AddRule => self.title_rules.rules.push(TitleRule::default()),
+			FocusMsg::RemoveRule(i) => rule_list::remove(&mut self.title_rules.rules, i),
+			FocusMsg::Class(i, v) => {
+				if let Some(rule) = self.title_rules.rules.get_mut(i) {
+					rule.class = v;
+				}
+			}
+			FocusMsg::Title(i, v) => {
+				if let Some(rule) = self.title_rules.rules.get_mut(i) {
+					rule.title = v;
+				}
+			}
+			FocusMsg::Details(i, v) => {
+				if let Some(rule) = self.title_rules.rules.get_mut(i) {
+					rule.details = v;
+				}
+			}
+			FocusMsg::State(i, v) => {
+				if let Some(rule) = self.title_rules.rules.get_mut(i) {
+					rule.state = v;
+				}
+			}
+		}
+
+		// edits are picked up on the next window change instead of on every keystroke
+		self.write_settings()
+	}
+	/// What the title rules fill in, `None` while a calendar event outranks them
+	fn title_rule_base(&self) -> Option<&Activity> {
+		if self.calendar.current.is_some() {
+			return None;
+		}
+		let process = self
+			.process_rules
+			.active
+			.and_then(|i| self.process_rules.rules.get(i));
+		let schedule = self
+			.schedule
+			.active
+			.and_then(|i| self.schedule.rules.get(i));
+
+		Some(
+			process
+				.map(|rule| &rule.activity)
+				.or(schedule.map(|rule| &rule.activity))
+				.unwrap_or(&self.activity),
+		)
+	}
+	/// Maps the focused window onto the current activity, or restores it once no rule matches
+	fn apply_title_rules(&mut self) -> Task<Message> {
+		let found = self
+			.title_rule_base()
+			.and_then(|base| self.title_rules.find(base));
+
+		let unchanged = match (&found, &self.title_rules.applied) {
+			(Some(found), Some(applied)) => {
+				found.details == applied.details && found.state == applied.state
+			}
+			(None, None) => true,
+			_ => false,
+		};
+		if unchanged {
+			return Task::none();
+		}
+		self.title_rules.applied = found.clone();
+		if !matches!(self.connection_state, ConnectionState::Connected) {
+			return Task::none();
+		}
+
+		match found {
+			Some(activity) => self.send_presence_msg(MainThreadMessage::SetActivity(activity)),
+			None => self.restore_presence_or(self.activity.clone()),
+		}
+	}
+	fn apply_now_playing(&mut self, was_playing: bool) -> Task<Message> {
+		if !matches!(self.connection_state, ConnectionState::Connected) {
+			return Task::none();
+		}
+
+		match &self.now_playing {
+			Some(track) => {
+				let activity = track.activity(&self.activity);
+				self.send_presence_msg(MainThreadMessage::SetActivity(activity))
+			}
+			None if was_playing && self.automation_active() => self.restore_presence(),
+			None if was_playing => self.send_presence_msg(MainThreadMessage::ClearActivity),
+			None => Task::none(),
+		}
+	}
+	fn update_pomodoro(&mut self, msg: PomodoroMsg) -> Task<Message> {
+		match msg {
+			PomodoroMsg::Start => {
+				let Some(phase) = self.pomodoro.start(&self.activity) else {
+					return Task::none();
+				};
+				self.send_tray_update(TrayUpdate::PomodoroLabel("Stop focus timer"));
+
+				self.play_phase(phase)
+			}
+			PomodoroMsg::Stop => {
+				self.pomodoro.stop();
+				self.send_tray_update(TrayUpdate::PomodoroLabel("Start focus timer"));
+				self.send_tray_update(TrayUpdate::Status(None));
+
+				match self.connection_state {
+					ConnectionState::Connected => self.restore_presence_or(self.activity.clone()),
+					_ => Task::none(),
+				}
+			}
+			PomodoroMsg::Toggle => {
+				if self.pomodoro.is_running() {
+					Task::done(PomodoroMsg::Stop.into())
+				} else {
+					Task::done(PomodoroMsg::Start.into())
+				}
+			}
+			PomodoroMsg::Next(generation) => {
+				match self.pomodoro.advance(generation, &self.activity) {
+					Some(phase) => self.play_phase(phase),
+					None => Task::none(),
+				}
+			}
+			PomodoroMsg::FocusMinutes(v) => {
+				self.pomodoro.focus_minutes = v;
+
+				self.write_settings()
+			}
+			PomodoroMsg::BreakMinutes(v) => {
+				self.pomodoro.break_minutes = v;
+
+				self.write_settings()
+			}
+			PomodoroMsg::LongBreakMinutes(v) => {
+				self.pomodoro.long_break_minutes = v;
+
+				self.write_settings()
+			}
+			PomodoroMsg::Cycles(v) => {
+				self.pomodoro.cycles = v;
+
+				self.write_settings()
+			}
+			PomodoroMsg::UseForFocus => {
+				self.pomodoro.focus_activity = Some(self.activity.clone());
+
+				self.write_settings()
+			}
+			PomodoroMsg::UseForBreak => {
+				self.pomodoro.break_activity = Some(self.activity.clone());
+
+				self.write_settings()
+			}
+			PomodoroMsg::ResetActivities => {
+				self.pomodoro.focus_activity = None;
+				self.pomodoro.break_activity = None;
+
+				self.write_settings()
+			}
+		}
+	}
+	fn play_phase(&mut self, phase: PhasePlayback) -> Task<Message> {
+		let PhasePlayback {
+			phase,
+			activity,
+			duration,
+			generation,
+		} = phase;
+		let status = format!(
+			"{} until {}",
+			self.pomodoro.status().unwrap_or(phase.label().to_string()),
+			(Local::now() + duration).format("%H:%M")
+		);
+		self.send_tray_update(TrayUpdate::Status(Some(status.clone())));
+
+		Task::batch([
+			self.notify(Level::Info, status),
+			self.send_presence_msg(MainThreadMessage::SetActivity(activity)),
+			Task::future(async move {
+				time::sleep(duration).await;
+				Message::from(PomodoroMsg::Next(generation))
+			}),
+		])
+	}
+	fn play_frame(&mut self, frame: FramePlayback) -> Task<Message> {
+		let FramePlayback {
+			activity,
+			duration,
+			generation,
+		} = frame;
+
+		Task::batch([
+			self.send_presence_msg(MainThreadMessage::SetActivity(activity)),
+			Task::future(async move {
+				time::sleep(duration).await;
+				Message::from(RotationMsg::Next(generation))
+			}),
+		])
+	}
+}
+
+/// Moves `timestamp` to `date`, keeping its time of day
+fn with_date(timestamp: Option<DateTime<Utc>>, date: Date) -> Result<DateTime<Utc>, String> {
+	let timestamp = timestamp.unwrap_or(Utc::now());
+	NaiveDate::from_ymd_opt(date.year, date.month, date.day)
+		.map(|v| v.and_time(timestamp.time()).and_utc())
+		.ok_or_else(|| {
+			format!(
+				"{}-{:02}-{:02} is not a valid date",
+				date.year, date.month, date.day
+			)
+		})
+}
+
+fn with_time(timestamp: Option<DateTime<Utc>>, time: Time) -> Option<DateTime<Utc>> {
+	let timestamp = timestamp.unwrap_or(Utc::now());
+	let Time::Hms {
+		hour,
+		minute,
+		second,
+		period: _,
+	} = time
+	else {
+		return None;
+	};
+	let timestamp = timestamp.with_hour(hour).unwrap();
+	let timestamp = timestamp.with_minute(minute).unwrap();
+	timestamp.with_second(second)
 }
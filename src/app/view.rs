@@ -1,9 +1,12 @@
-use chrono::{Datelike, Timelike, Utc};
-use iced::widget::{column, row};
+use chrono::{Datelike, Local, Timelike, Utc};
+use iced::widget::{column, row, stack};
 use iced::{
 	Alignment, Element, Length,
 	alignment::{Horizontal, Vertical},
-	widget::{button, container, pick_list, radio, text, text_input},
+	padding,
+	widget::{
+		Row, button, container, image, pick_list, radio, scrollable, text, text_input, toggler,
+	},
 	window::Id,
 };
 use iced_aw::date_picker::Date;
@@ -16,45 +19,286 @@ use iced_aw::{
 
 use crate::{
 	ACTIVITY_TYPES, TEXT_COLUMN_WIDTH,
-	activity::TimestampType,
+	activity::{Activity, IMAGE_SOURCES, ImageSource, TIMESTAMP_TYPES, TimestampType},
 	app::{
 		App, ConnectionState,
-		message::{ActivityMsg, Message},
+		message::{
+			ActivityMsg, AssetMsg, AwayMsg, CalendarMsg, DetectionMsg, FocusMsg, ImageSlot,
+			Message, NotificationMsg, PomodoroMsg, PresetMsg, RotationMsg, ScheduleMsg,
+			SettingsMsg,
+		},
 	},
+	idle::{AWAY_ACTIONS, MAX_TIMEOUT_MINUTES, MIN_TIMEOUT_MINUTES},
+	notification::Level,
+	pomodoro::{MAX_CYCLES, MAX_PHASE_MINUTES, MIN_PHASE_MINUTES},
+	rotation::{MAX_FRAME_DURATION, MIN_FRAME_DURATION},
+	schedule::{TimeOfDay, WEEKDAYS},
+	settings::{DEFAULT_API_PORT, LOG_LEVELS, THEMES, autostart_file},
+	validation::{self, Field},
 };
 
+const TOAST_WIDTH: f32 = 300.;
+
 impl App {
-	pub fn view(&self, _window: Id) -> Element<'_, Message> {
+	pub fn view(&self, window: Id) -> Element<'_, Message> {
+		if self.settings_window == Some(window) {
+			return self.settings_view();
+		}
+
 		let menu_tpl = |items| Menu::new(items).max_width(50.).offset(15.0);
 		#[rustfmt::skip]
 		let mb = menu_bar!(
         (menu_button("File"), {menu_tpl(menu_items!(
+            (b("New", Message::NewActivity))
             (b("Save", Message::SaveActivity))
             (b("Open", Message::OpenActivity))
+            (b("Settings", Message::OpenSettings))
+            (b("Notifications", NotificationMsg::TogglePanel.into()))
             (b("Exit", Message::Exit))
         ))})
     );
 
-		column![
+		let content = column![
 			mb,
-			column![
-				self.id_row(),
-				self.details_row(),
-				self.state_row(),
-				self.timestamp_row(),
-				self.image_row(),
-				self.button_row(),
-				self.connect_row(),
-			]
+			row![
+				self.preset_sidebar(),
+				// too many sections to fit a normal window
+				scrollable(
+					column![
+						self.id_row(),
+						self.details_row(),
+						self.state_row(),
+						self.timestamp_row(),
+						self.image_row(),
+						self.button_row(),
+						self.rotation_row(),
+						self.pomodoro_row(),
+						self.schedule_row(),
+						self.calendar_row(),
+						self.away_row(),
+						self.detection_row(),
+						self.focus_row(),
+						self.connect_row(),
+					]
+					.padding(10.)
+					.spacing(10.)
+					.align_x(Horizontal::Center),
+				)
+				.height(Length::Fill),
+				column![self.preview()]
+					.push(self.show_notifications.then(|| self.notification_panel()))
+					.padding(10.)
+					.spacing(10.),
+			],
+			row![]
+		]
+		.width(Length::Fill)
+		.height(Length::Fill);
+
+		stack![content, self.toasts()].into()
+	}
+	/// Latest notifications that werent dismissed, in the bottom right corner
+	fn toasts(&self) -> Element<'_, Message, iced::Theme, iced::Renderer> {
+		let toasts =
+			self.notifications
+				.toasts()
+				.fold(column![].spacing(5.), |toasts, notification| {
+					let style = match notification.level {
+						Level::Info => container::rounded_box,
+						Level::Error => container::danger,
+					};
+					toasts.push(
+						container(
+							row![
+								text(&notification.text).width(Length::Fill),
+								button("Dismiss")
+									.style(button::text)
+									.on_press(NotificationMsg::Dismiss(notification.id).into()),
+							]
+							.spacing(10.)
+							.align_y(Vertical::Center),
+						)
+						.padding(10.)
+						.width(Length::Fixed(TOAST_WIDTH))
+						.style(style),
+					)
+				});
+
+		container(toasts)
 			.padding(10.)
+			.width(Length::Fill)
+			.height(Length::Fill)
+			.align_x(Horizontal::Right)
+			.align_y(Vertical::Bottom)
+			.into()
+	}
+	fn notification_panel(&self) -> Element<'_, Message, iced::Theme, iced::Renderer> {
+		let history = self.notifications.history.iter().fold(
+			column![].spacing(5.),
+			|history, notification| {
+				let line = text(format!(
+					"{} {}",
+					notification.time.format("%H:%M:%S"),
+					notification.text
+				))
+				.size(12);
+				history.push(match notification.level {
+					Level::Info => line,
+					Level::Error => line.style(text::danger),
+				})
+			},
+		);
+
+		column![
+			row![
+				text("Notifications").width(Length::Fill),
+				button("Dismiss all").on_press(NotificationMsg::DismissAll.into()),
+				button("Clear").on_press(NotificationMsg::Clear.into()),
+			]
 			.spacing(10.)
-			.align_x(Horizontal::Center),
-			row![]
+			.align_y(Vertical::Center),
+			scrollable(history).height(Length::Fixed(200.)),
 		]
+		.spacing(10.)
+		.width(Length::Fixed(TOAST_WIDTH))
+		.into()
+	}
+	fn settings_view(&self) -> Element<'_, Message> {
+		let settings = &self.settings;
+		let setting_row = |label, widget: Element<'static, Message>| {
+			row![
+				text(label)
+					.align_x(Alignment::End)
+					.width(Length::Fixed(160.)),
+				widget
+			]
+			.spacing(10.)
+			.align_y(Vertical::Center)
+		};
+
+		column![
+			toggler(settings.auto_connect)
+				.label("Connect and apply activity on launch")
+				.on_toggle(|v| SettingsMsg::AutoConnect(v).into()),
+			toggler(settings.autostart)
+				.label("Launch at login")
+				.on_toggle_maybe(autostart_file().map(|_| |v| SettingsMsg::Autostart(v).into())),
+			toggler(settings.mpris)
+				.label("Show music playing in MPRIS players")
+				.on_toggle(|v| SettingsMsg::Mpris(v).into()),
+			toggler(settings.template_refresh)
+				.label("Refresh placeholders like {time} every minute")
+				.on_toggle(|v| SettingsMsg::TemplateRefresh(v).into()),
+			toggler(settings.desktop_notifications)
+				.label("Desktop notifications while the window is closed")
+				.on_toggle(|v| SettingsMsg::DesktopNotifications(v).into()),
+			toggler(settings.start_minimized)
+				.label("Start minimized to tray")
+				.on_toggle(|v| SettingsMsg::StartMinimized(v).into()),
+			toggler(settings.remember_window)
+				.label("Remember window size and position")
+				.on_toggle(|v| SettingsMsg::RememberWindow(v).into()),
+			setting_row(
+				"Default timestamp",
+				pick_list(TIMESTAMP_TYPES, Some(settings.default_timestamp), |v| {
+					SettingsMsg::DefaultTimestamp(v).into()
+				})
+				.into()
+			),
+			setting_row(
+				"Theme",
+				pick_list(THEMES, Some(settings.theme), |v| SettingsMsg::Theme(v)
+					.into())
+				.into()
+			),
+			setting_row(
+				"Log level",
+				pick_list(LOG_LEVELS, Some(settings.log_level), |v| {
+					SettingsMsg::LogLevel(v).into()
+				})
+				.into()
+			),
+			row![
+				toggler(settings.api_port.is_some())
+					.label("HTTP API on port")
+					.on_toggle(|v| SettingsMsg::ApiEnabled(v).into()),
+				number_input(
+					&settings.api_port.unwrap_or(DEFAULT_API_PORT),
+					1024..=u16::MAX,
+					|v| SettingsMsg::ApiPort(v).into(),
+				),
+			]
+			.spacing(10.)
+			.align_y(Vertical::Center),
+			text("Log level and API changes apply after a restart").size(12.),
+		]
+		.padding(20.)
+		.spacing(15.)
 		.width(Length::Fill)
 		.height(Length::Fill)
 		.into()
 	}
+	fn preset_sidebar(&self) -> Element<'_, Message, iced::Theme, iced::Renderer> {
+		let list =
+			self.presets
+				.iter()
+				.enumerate()
+				.fold(column![].spacing(5.), |list, (i, preset)| {
+					let style = if self.selected_preset == Some(i) {
+						button::primary
+					} else {
+						button::secondary
+					};
+					list.push(
+						button(text(preset.name.as_str()))
+							.on_press(PresetMsg::Select(i).into())
+							.style(style)
+							.width(Length::Fill),
+					)
+				});
+
+		let selected = self.selected_preset.filter(|i| *i < self.presets.len());
+		let name = text_input(
+			"Name",
+			selected
+				.map(|i| self.presets[i].name.as_str())
+				.unwrap_or_default(),
+		)
+		.on_input_maybe(selected.map(|_| |v: String| Message::from(PresetMsg::Rename(v))));
+		let on_selected = |msg: PresetMsg| selected.map(|_| Message::from(msg));
+
+		column![
+			text("Presets"),
+			scrollable(list).height(Length::Fill),
+			name,
+			row![
+				button("New")
+					.on_press(PresetMsg::New.into())
+					.width(Length::Fill),
+				button("Duplicate")
+					.on_press_maybe(on_selected(PresetMsg::Duplicate))
+					.width(Length::Fill),
+				button("Delete")
+					.on_press_maybe(on_selected(PresetMsg::Delete))
+					.width(Length::Fill),
+			]
+			.spacing(5.),
+			row![
+				button("Save")
+					.on_press_maybe(on_selected(PresetMsg::Save))
+					.width(Length::Fill),
+				button("Apply")
+					.on_press_maybe(selected.map(|i| PresetMsg::Apply(i).into()))
+					.width(Length::Fill),
+			]
+			.spacing(5.),
+		]
+		.padding(10.)
+		.spacing(10.)
+		.width(Length::Fixed(220.))
+		.height(Length::Fill)
+		.into()
+	}
 	fn id_row(&self) -> Element<'_, Message, iced::Theme, iced::Renderer> {
 		row![
 			text("ID")
@@ -75,7 +319,7 @@ impl App {
 		.into()
 	}
 	fn details_row(&self) -> Element<'_, Message, iced::Theme, iced::Renderer> {
-		row![
+		let details = row![
 			text("Details")
 				.align_x(Alignment::End)
 				.width(Length::Fixed(TEXT_COLUMN_WIDTH)),
@@ -84,11 +328,15 @@ impl App {
 		]
 		.spacing(10.)
 		.align_y(Vertical::Center)
-		.width(Length::Fill)
-		.into()
+		.width(Length::Fill);
+
+		column![details]
+			.push(self.field_errors(&[Field::Details]))
+			.spacing(5.)
+			.into()
 	}
 	fn state_row(&self) -> Element<'_, Message, iced::Theme, iced::Renderer> {
-		row![
+		let state = row![
 			text("State")
 				.align_x(Alignment::End)
 				.width(Length::Fixed(TEXT_COLUMN_WIDTH)),
@@ -109,38 +357,16 @@ impl App {
 		]
 		.spacing(10.)
 		.align_y(Vertical::Center)
-		.width(Length::Fill)
-		.into()
+		.width(Length::Fill);
+
+		column![state]
+			.push(self.field_errors(&[Field::State, Field::Party]))
+			.spacing(5.)
+			.into()
 	}
 	fn timestamp_row(&self) -> Element<'_, Message, iced::Theme, iced::Renderer> {
-		let open_date_picker = button("Choose date").on_press(Message::ChooseDate);
-		let open_time_picker = button("Choose time").on_press(Message::ChooseTime);
-		let date = self.activity.custom_timestamp.unwrap_or(Utc::now());
-		let custom_date = date_picker(
-			self.show_date_picker,
-			Date {
-				year: date.year(),
-				month: date.month(),
-				day: date.day(),
-			},
-			open_date_picker,
-			Message::CancelDate,
-			|v| ActivityMsg::CustomDate(v).into(),
-		);
-		let custom_time = time_picker(
-			self.show_time_picker,
-			Time::Hms {
-				hour: date.hour(),
-				minute: date.minute(),
-				second: date.second(),
-				period: Period::H24,
-			},
-			open_time_picker,
-			Message::CancelTime,
-			|v| ActivityMsg::CustomTime(v).into(),
-		)
-		.use_24h()
-		.show_seconds();
+		let (custom_date, custom_time) = self.timestamp_pickers(false);
+		let (end_date, end_time) = self.timestamp_pickers(true);
 		row![
 			text("Timestamp")
 				.align_x(Alignment::End)
@@ -178,6 +404,34 @@ impl App {
 				]
 				.align_y(Vertical::Center)
 				.spacing(10.),
+				row![
+					radio(
+						"Ends at",
+						TimestampType::EndsAt,
+						Some(self.activity.timestamp_type),
+						|v| ActivityMsg::TimestampType(v).into()
+					),
+					end_date,
+					end_time
+				]
+				.align_y(Vertical::Center)
+				.spacing(10.),
+				row![
+					radio(
+						"Countdown",
+						TimestampType::Countdown,
+						Some(self.activity.timestamp_type),
+						|v| ActivityMsg::TimestampType(v).into()
+					),
+					number_input(
+						&(self.activity.countdown.unwrap_or_default() / 60),
+						0..=24 * 60,
+						|v| ActivityMsg::Countdown(v).into(),
+					),
+					text("minutes"),
+				]
+				.align_y(Vertical::Center)
+				.spacing(10.),
 			]
 			.width(Length::Fill)
 			.spacing(10.)
@@ -187,16 +441,463 @@ impl App {
 		.align_y(Vertical::Bottom)
 		.into()
 	}
+	/// Date and time pickers for the start, or with `end` for the end timestamp
+	fn timestamp_pickers(
+		&self,
+		end: bool,
+	) -> (
+		Element<'_, Message, iced::Theme, iced::Renderer>,
+		Element<'_, Message, iced::Theme, iced::Renderer>,
+	) {
+		let (timestamp, show_date, show_time) = if end {
+			(
+				self.activity.end_timestamp,
+				self.show_end_date_picker,
+				self.show_end_time_picker,
+			)
+		} else {
+			(
+				self.activity.custom_timestamp,
+				self.show_date_picker,
+				self.show_time_picker,
+			)
+		};
+		let (choose_date, choose_time, cancel_date, cancel_time) = if end {
+			(
+				Message::ChooseEndDate,
+				Message::ChooseEndTime,
+				Message::CancelEndDate,
+				Message::CancelEndTime,
+			)
+		} else {
+			(
+				Message::ChooseDate,
+				Message::ChooseTime,
+				Message::CancelDate,
+				Message::CancelTime,
+			)
+		};
+
+		let open_date_picker = button("Choose date").on_press(choose_date);
+		let open_time_picker = button("Choose time").on_press(choose_time);
+		let date = timestamp.unwrap_or(Utc::now());
+		let pick_date = date_picker(
+			show_date,
+			Date {
+				year: date.year(),
+				month: date.month(),
+				day: date.day(),
+			},
+			open_date_picker,
+			cancel_date,
+			move |v| {
+				if end {
+					ActivityMsg::EndDate(v).into()
+				} else {
+					ActivityMsg::CustomDate(v).into()
+				}
+			},
+		);
+		let pick_time = time_picker(
+			show_time,
+			Time::Hms {
+				hour: date.hour(),
+				minute: date.minute(),
+				second: date.second(),
+				period: Period::H24,
+			},
+			open_time_picker,
+			cancel_time,
+			move |v| {
+				if end {
+					ActivityMsg::EndTime(v).into()
+				} else {
+					ActivityMsg::CustomTime(v).into()
+				}
+			},
+		)
+		.use_24h()
+		.show_seconds();
+
+		(pick_date.into(), pick_time.into())
+	}
+	fn rotation_row(&self) -> Element<'_, Message, iced::Theme, iced::Renderer> {
+		let frames = self.rotation.frames.iter().enumerate().fold(
+			column![].spacing(5.),
+			|list, (i, frame)| {
+				list.push(rule_list_row(
+					frame.activity.label("Frame", i),
+					[
+						number_input(
+							&frame.duration,
+							MIN_FRAME_DURATION..=MAX_FRAME_DURATION,
+							move |v| RotationMsg::Duration(i, v).into(),
+						)
+						.into(),
+						text("s").into(),
+					],
+					i,
+					RotationMsg::MoveUp(i).into(),
+					RotationMsg::RemoveFrame(i).into(),
+				))
+			},
+		);
+		let (toggle_text, toggle_msg) = if self.rotation.is_running() {
+			("Stop rotation", RotationMsg::Stop)
+		} else {
+			("Start rotation", RotationMsg::Start)
+		};
+
+		row![
+			text("Rotation")
+				.align_x(Alignment::End)
+				.width(Length::Fixed(TEXT_COLUMN_WIDTH)),
+			column![
+				frames,
+				row![
+					button("Add current activity").on_press(RotationMsg::AddFrame.into()),
+					button(toggle_text).on_press_maybe(
+						(!self.rotation.frames.is_empty()).then_some(toggle_msg.into())
+					),
+				]
+				.spacing(10.),
+			]
+			.spacing(10.)
+			.width(Length::Fill),
+		]
+		.spacing(10.)
+		.width(Length::Fill)
+		.into()
+	}
+	fn pomodoro_row(&self) -> Element<'_, Message, iced::Theme, iced::Renderer> {
+		let pomodoro = &self.pomodoro;
+		let minutes = |value: u64, msg: fn(u64) -> PomodoroMsg| {
+			number_input(&value, MIN_PHASE_MINUTES..=MAX_PHASE_MINUTES, move |v| {
+				msg(v).into()
+			})
+		};
+		let (toggle_text, toggle_msg) = if pomodoro.is_running() {
+			("Stop", PomodoroMsg::Stop)
+		} else {
+			("Start", PomodoroMsg::Start)
+		};
+		let custom = pomodoro.focus_activity.is_some() || pomodoro.break_activity.is_some();
+
+		row![
+			text("Focus timer")
+				.align_x(Alignment::End)
+				.width(Length::Fixed(TEXT_COLUMN_WIDTH)),
+			column![
+				row![
+					text("Focus"),
+					minutes(pomodoro.focus_minutes, PomodoroMsg::FocusMinutes),
+					text("Break"),
+					minutes(pomodoro.break_minutes, PomodoroMsg::BreakMinutes),
+					text("Long break"),
+					minutes(pomodoro.long_break_minutes, PomodoroMsg::LongBreakMinutes),
+					text("min, every"),
+					number_input(&pomodoro.cycles, 1..=MAX_CYCLES, |v| {
+						PomodoroMsg::Cycles(v).into()
+					}),
+				]
+				.spacing(10.)
+				.align_y(Vertical::Center),
+				row![
+					button("Use current for focus").on_press(PomodoroMsg::UseForFocus.into()),
+					button("Use current for breaks").on_press(PomodoroMsg::UseForBreak.into()),
+					button("Reset")
+						.on_press_maybe(custom.then_some(PomodoroMsg::ResetActivities.into())),
+					button(toggle_text).on_press(toggle_msg.into()),
+					text(pomodoro.status().unwrap_or_default()),
+				]
+				.spacing(10.)
+				.align_y(Vertical::Center),
+			]
+			.spacing(10.)
+			.width(Length::Fill),
+		]
+		.spacing(10.)
+		.width(Length::Fill)
+		.into()
+	}
+	fn schedule_row(&self) -> Element<'_, Message, iced::Theme, iced::Renderer> {
+		let schedule = &self.schedule;
+		let rules =
+			schedule
+				.rules
+				.iter()
+				.enumerate()
+				.fold(column![].spacing(5.), |list, (i, rule)| {
+					let days = WEEKDAYS.iter().fold(row![].spacing(2.), |days, day| {
+						let style = if rule.has_day(*day) {
+							button::primary
+						} else {
+							button::secondary
+						};
+						days.push(
+							button(text(day.to_string()))
+								.style(style)
+								.on_press(ScheduleMsg::ToggleDay(i, *day).into()),
+						)
+					});
+					list.push(rule_list_row(
+						active_label(&rule.activity, i, schedule.active),
+						[
+							days.into(),
+							pick_list(TimeOfDay::all(), Some(rule.start), move |v| {
+								ScheduleMsg::Start(i, v).into()
+							})
+							.into(),
+							text("to").into(),
+							pick_list(TimeOfDay::all(), Some(rule.end), move |v| {
+								ScheduleMsg::End(i, v).into()
+							})
+							.into(),
+						],
+						i,
+						ScheduleMsg::MoveUp(i).into(),
+						ScheduleMsg::RemoveRule(i).into(),
+					))
+				});
+		let name = |index: Option<usize>| {
+			index
+				.and_then(|i| {
+					schedule
+						.rules
+						.get(i)
+						.map(|rule| rule.activity.label("Rule", i))
+				})
+				.unwrap_or_else(|| String::from("current activity"))
+		};
+		let now = Local::now().naive_local();
+		let preview = match schedule.next_change(now) {
+			Some((at, next)) => format!(
+				"Now: {}, then {} at {}",
+				name(schedule.active_at(now)),
+				name(next),
+				at.format("%a %H:%M")
+			),
+			None => format!("Now: {}", name(schedule.active_at(now))),
+		};
+
+		row![
+			text("Schedule")
+				.align_x(Alignment::End)
+				.width(Length::Fixed(TEXT_COLUMN_WIDTH)),
+			column![
+				rules,
+				rule_list_controls(
+					ScheduleMsg::AddRule.into(),
+					schedule.enabled,
+					"Switch activity on a weekly schedule",
+					|v| ScheduleMsg::Enabled(v).into(),
+				)
+				.push(text(preview)),
+			]
+			.spacing(10.)
+			.width(Length::Fill),
+		]
+		.spacing(10.)
+		.width(Length::Fill)
+		.into()
+	}
+	fn calendar_row(&self) -> Element<'_, Message, iced::Theme, iced::Renderer> {
+		let calendar = &self.calendar;
+		let now = Utc::now();
+		let status = match (&calendar.error, &calendar.current, calendar.upcoming(now)) {
+			(Some(err), _, _) => text(err).style(text::danger),
+			(None, Some(event), _) => text(format!(
+				"In {} until {}",
+				event.summary,
+				event.end.with_timezone(&Local).format("%H:%M")
+			)),
+			(None, None, Some(event)) => text(format!(
+				"Next: {} on {}",
+				event.summary,
+				event.start.with_timezone(&Local).format("%a %H:%M")
+			)),
+			(None, None, None) if calendar.enabled => text("No upcoming events"),
+			(None, None, None) => text(""),
+		};
+
+		row![
+			text("Calendar")
+				.align_x(Alignment::End)
+				.width(Length::Fixed(TEXT_COLUMN_WIDTH)),
+			column![
+				row![
+					text_input("path/to/calendar.ics", &calendar.path)
+						.on_input(|v| CalendarMsg::Path(v).into()),
+					button("Browse").on_press(CalendarMsg::Browse.into()),
+					button("Reload")
+						.on_press_maybe(calendar.enabled.then_some(CalendarMsg::Reload.into())),
+				]
+				.spacing(10.)
+				.align_y(Vertical::Center),
+				row![
+					text_input("Image key", &calendar.image_key)
+						.on_input(|v| CalendarMsg::ImageKey(v).into())
+						.width(Length::Fixed(200.)),
+					toggler(calendar.redact)
+						.label("Hide event titles")
+						.on_toggle(|v| CalendarMsg::Redact(v).into()),
+					toggler(calendar.enabled)
+						.label("Set activity from calendar events")
+						.on_toggle(|v| CalendarMsg::Enabled(v).into()),
+				]
+				.spacing(10.)
+				.align_y(Vertical::Center),
+				status,
+			]
+			.spacing(10.)
+			.width(Length::Fill),
+		]
+		.spacing(10.)
+		.width(Length::Fill)
+		.into()
+	}
+	fn away_row(&self) -> Element<'_, Message, iced::Theme, iced::Renderer> {
+		let away = &self.away;
+
+		row![
+			text("Away")
+				.align_x(Alignment::End)
+				.width(Length::Fixed(TEXT_COLUMN_WIDTH)),
+			column![
+				row![
+					text("After"),
+					number_input(
+						&away.timeout_minutes,
+						MIN_TIMEOUT_MINUTES..=MAX_TIMEOUT_MINUTES,
+						|v| AwayMsg::TimeoutMinutes(v).into()
+					),
+					text("min idle"),
+					pick_list(AWAY_ACTIONS, Some(away.action), |v| {
+						AwayMsg::Action(v).into()
+					}),
+				]
+				.spacing(10.)
+				.align_y(Vertical::Center),
+				row![
+					button("Use current activity").on_press(AwayMsg::UseCurrent.into()),
+					button("Reset")
+						.on_press_maybe(away.activity.is_some().then_some(AwayMsg::Reset.into())),
+					toggler(away.enabled)
+						.label("Switch when idle")
+						.on_toggle(|v| AwayMsg::Enabled(v).into()),
+					text(if away.active { "Away" } else { "" }),
+				]
+				.spacing(10.)
+				.align_y(Vertical::Center),
+			]
+			.spacing(10.)
+			.width(Length::Fill),
+		]
+		.spacing(10.)
+		.width(Length::Fill)
+		.into()
+	}
+	fn detection_row(&self) -> Element<'_, Message, iced::Theme, iced::Renderer> {
+		let rules = self.process_rules.rules.iter().enumerate().fold(
+			column![].spacing(5.),
+			|list, (i, rule)| {
+				list.push(rule_list_row(
+					active_label(&rule.activity, i, self.process_rules.active),
+					[text_input("nvim, code", &rule.processes)
+						.on_input(move |v| DetectionMsg::Processes(i, v).into())
+						.width(Length::Fixed(200.))
+						.into()],
+					i,
+					DetectionMsg::MoveUp(i).into(),
+					DetectionMsg::RemoveRule(i).into(),
+				))
+			},
+		);
+
+		row![
+			text("Processes")
+				.align_x(Alignment::End)
+				.width(Length::Fixed(TEXT_COLUMN_WIDTH)),
+			column![
+				rules,
+				rule_list_controls(
+					DetectionMsg::AddRule.into(),
+					self.process_rules.enabled,
+					"Switch activity by running process",
+					|v| DetectionMsg::Enabled(v).into(),
+				),
+			]
+			.spacing(10.)
+			.width(Length::Fill),
+		]
+		.spacing(10.)
+		.width(Length::Fill)
+		.into()
+	}
+	fn focus_row(&self) -> Element<'_, Message, iced::Theme, iced::Renderer> {
+		let rules = self.title_rules.rules.iter().enumerate().fold(
+			column![].spacing(5.),
+			|list, (i, rule)| {
+				let rule_row = row![
+					text_input("Class", &rule.class)
+						.on_input(move |v| FocusMsg::Class(i, v).into()),
+					text_input("Title", &rule.title)
+						.on_input(move |v| FocusMsg::Title(i, v).into()),
+					text_input("Details", &rule.details)
+						.on_input(move |v| FocusMsg::Details(i, v).into()),
+					text_input("State", &rule.state)
+						.on_input(move |v| FocusMsg::State(i, v).into()),
+					button("Remove").on_press(FocusMsg::RemoveRule(i).into()),
+				]
+				.spacing(10.)
+				.align_y(Vertical::Center);
+
+				match rule.validate() {
+					Ok(()) => list.push(rule_row),
+					Err(err) => list
+						.push(rule_row)
+						.push(text(err.to_string()).style(text::danger)),
+				}
+			},
+		);
+
+		row![
+			text("Window")
+				.align_x(Alignment::End)
+				.width(Length::Fixed(TEXT_COLUMN_WIDTH)),
+			column![
+				rules,
+				row![
+					button("Add rule").on_press(FocusMsg::AddRule.into()),
+					toggler(self.title_rules.enabled)
+						.label("Fill details and state from the focused window")
+						.on_toggle(|v| FocusMsg::Enabled(v).into()),
+				]
+				.spacing(10.)
+				.align_y(Vertical::Center),
+			]
+			.spacing(10.)
+			.width(Length::Fill),
+		]
+		.spacing(10.)
+		.width(Length::Fill)
+		.into()
+	}
 	fn connect_row(&self) -> Element<'_, Message, iced::Theme, iced::Renderer> {
 		let (text, msg) = match self.connection_state {
 			ConnectionState::Disconnected => ("Connect", Message::Connect),
 			ConnectionState::Connecting => ("Connecting", Message::None),
 			ConnectionState::Connected => ("Disconnect", Message::Disconnect),
+			ConnectionState::Reconnecting => ("Reconnecting, click to stop", Message::Disconnect),
 		};
 		container(
 			row![
 				button(text).on_press(msg),
-				button("Set activity").on_press(Message::SetActivity)
+				button("Set activity").on_press_maybe(
+					validation::validate(&self.activity)
+						.is_valid()
+						.then_some(Message::SetActivity)
+				)
 			]
 			.spacing(10.),
 		)
@@ -235,15 +936,21 @@ impl App {
 		row![
 			self.activity_image(
 				"Big image",
+				ImageSlot::Large,
+				self.activity.large_source,
 				self.activity.large_key.as_ref().unwrap_or(&String::new()),
 				self.activity.large_text.as_ref().unwrap_or(&String::new()),
+				|v| ActivityMsg::LargeImageSource(v).into(),
 				|v| ActivityMsg::LargeImageKey(v).into(),
 				|v| ActivityMsg::LargeImageText(v).into(),
 			),
 			self.activity_image(
 				"Small image",
+				ImageSlot::Small,
+				self.activity.small_source,
 				self.activity.small_key.as_ref().unwrap_or(&String::new()),
 				self.activity.small_text.as_ref().unwrap_or(&String::new()),
+				|v| ActivityMsg::SmallImageSource(v).into(),
 				|v| ActivityMsg::SmallImageKey(v).into(),
 				|v| ActivityMsg::SmallImageText(v).into(),
 			)
@@ -252,7 +959,7 @@ impl App {
 		.into()
 	}
 	fn activity_button<'a>(
-		&self,
+		&'a self,
 		nr: u8,
 		btn_text: &str,
 		btn_url: &str,
@@ -271,6 +978,9 @@ impl App {
 				text_input("", btn_text).on_input(text_msg),
 			]
 			.spacing(10),
+		]
+		.push(self.field_errors(&[Field::ButtonText(nr)]))
+		.push(
 			row![
 				text("URL")
 					.width(Length::Fixed(TEXT_COLUMN_WIDTH))
@@ -278,31 +988,56 @@ impl App {
 				text_input("", btn_url).on_input(url_msg),
 			]
 			.spacing(10),
-		]
+		)
+		.push(self.field_errors(&[Field::ButtonUrl(nr)]))
 		.spacing(10)
 		.width(Length::Fill)
 		.into()
 	}
+	#[allow(clippy::too_many_arguments)]
 	fn activity_image<'a>(
-		&self,
+		&'a self,
 		image_name: &'a str,
+		slot: ImageSlot,
+		source: ImageSource,
 		image_key: &str,
 		image_text: &str,
+		source_msg: impl Fn(ImageSource) -> Message + 'a,
 		key_msg: impl Fn(String) -> Message + 'a,
 		text_msg: impl Fn(String) -> Message + 'a,
 	) -> Element<'a, Message, iced::Theme, iced::Renderer> {
+		let (image_field, text_field) = match slot {
+			ImageSlot::Large => (Field::LargeImage, Field::LargeText),
+			ImageSlot::Small => (Field::SmallImage, Field::SmallText),
+		};
+		let (label, key) = match source {
+			ImageSource::Asset => ("Key", self.asset_picker(slot, image_key, key_msg)),
+			ImageSource::Url => ("URL", self.image_url(image_key, key_msg)),
+		};
+
 		column![
 			text(image_name)
 				.align_x(Alignment::End)
 				.width(Length::Fixed(TEXT_COLUMN_WIDTH))
 				.align_x(Alignment::End),
 			row![
-				text("Key")
+				text("Source")
 					.width(Length::Fixed(TEXT_COLUMN_WIDTH))
 					.align_x(Alignment::End),
-				text_input("", image_key).on_input(key_msg),
+				pick_list(IMAGE_SOURCES, Some(source), source_msg),
+			]
+			.spacing(10)
+			.align_y(Vertical::Center),
+			row![
+				text(label)
+					.width(Length::Fixed(TEXT_COLUMN_WIDTH))
+					.align_x(Alignment::End),
+				key,
 			]
 			.spacing(10),
+		]
+		.push(self.field_errors(&[image_field]))
+		.push(
 			row![
 				text("Text")
 					.width(Length::Fixed(TEXT_COLUMN_WIDTH))
@@ -310,13 +1045,212 @@ impl App {
 				text_input("", image_text).on_input(text_msg),
 			]
 			.spacing(10),
-		]
+		)
+		.push(self.field_errors(&[text_field]))
 		.spacing(10)
 		.width(Length::Fill)
 		.into()
 	}
+	/// Searchable list of the cached assets, a plain text field while there are none
+	fn asset_picker<'a>(
+		&'a self,
+		slot: ImageSlot,
+		image_key: &str,
+		key_msg: impl Fn(String) -> Message + 'a,
+	) -> Element<'a, Message, iced::Theme, iced::Renderer> {
+		let import = button(if self.importing_assets {
+			"Importing..."
+		} else {
+			"Import assets"
+		})
+		.on_press_maybe(
+			(self.assets.app_id.is_some() && !self.importing_assets)
+				.then_some(AssetMsg::Import.into()),
+		);
+		let error = self
+			.assets
+			.error
+			.as_ref()
+			.map(|err| text(err).style(text::danger));
+
+		if self.assets.assets.is_empty() {
+			return column![
+				row![text_input("", image_key).on_input(key_msg), import]
+					.spacing(10)
+					.align_y(Vertical::Center),
+			]
+			.push(error)
+			.spacing(5)
+			.into();
+		}
+
+		let selected = row![
+			self.asset_thumbnail(image_key, 24.),
+			text(if image_key.is_empty() {
+				String::from("Pick an image")
+			} else {
+				image_key.to_string()
+			}),
+		]
+		.spacing(10)
+		.align_y(Vertical::Center);
+		let header = row![
+			button(selected)
+				.style(button::secondary)
+				.width(Length::Fill)
+				.on_press(AssetMsg::Toggle(slot).into()),
+			import,
+		]
+		.spacing(10)
+		.align_y(Vertical::Center);
+		if self.asset_picker != Some(slot) {
+			return column![header].push(error).spacing(5).into();
+		}
+
+		let search = self.asset_search.trim();
+		let matches = self
+			.assets
+			.search(search)
+			.fold(column![].spacing(2), |list, asset| {
+				list.push(
+					button(
+						row![self.asset_thumbnail(&asset.key, 32.), text(&asset.key)]
+							.spacing(10)
+							.align_y(Vertical::Center),
+					)
+					.style(button::text)
+					.width(Length::Fill)
+					.on_press(AssetMsg::Pick(asset.key.clone()).into()),
+				)
+			});
+		// keys that are not in the list, like ones added after the import
+		let custom = (!search.is_empty() && self.assets.find(search).is_none()).then(|| {
+			button(text(format!("Use \"{search}\"")))
+				.style(button::text)
+				.on_press(AssetMsg::Pick(search.to_string()).into())
+		});
+
+		column![
+			header,
+			text_input("Search", &self.asset_search).on_input(|v| AssetMsg::Search(v).into()),
+			scrollable(column![matches].push(custom)).height(Length::Fixed(200.)),
+		]
+		.push(error)
+		.spacing(5)
+		.into()
+	}
+	/// Problems with `fields` of the edited activity, lined up under the inputs
+	fn field_errors(
+		&self,
+		fields: &[Field],
+	) -> Option<Element<'_, Message, iced::Theme, iced::Renderer>> {
+		// templates are filled in right before sending, the presence thread checks the result again
+		let validation = validation::validate(&self.activity);
+		let errors: Vec<_> = fields
+			.iter()
+			.filter_map(|field| validation.get(*field))
+			.collect();
+		if errors.is_empty() {
+			return None;
+		}
+
+		let errors = errors
+			.into_iter()
+			.fold(column![].spacing(2.), |errors, err| {
+				errors.push(text(err.to_string()).size(12).style(text::danger))
+			});
+		Some(
+			container(errors)
+				.padding(padding::left(TEXT_COLUMN_WIDTH + 10.))
+				.into(),
+		)
+	}
+	/// Field for an image URL with a preview of the downloaded image
+	fn image_url<'a>(
+		&'a self,
+		url: &str,
+		key_msg: impl Fn(String) -> Message + 'a,
+	) -> Element<'a, Message, iced::Theme, iced::Renderer> {
+		let thumbnail: Element<'a, Message, iced::Theme, iced::Renderer> =
+			match self.thumbnails.get(url) {
+				Some(path) => image(path)
+					.width(Length::Fixed(24.))
+					.height(Length::Fixed(24.))
+					.into(),
+				None => container(column![])
+					.width(Length::Fixed(24.))
+					.height(Length::Fixed(24.))
+					.into(),
+			};
+		row![thumbnail, text_input("https://", url).on_input(key_msg)]
+			.spacing(10)
+			.align_y(Vertical::Center)
+			.into()
+	}
+	fn asset_thumbnail(
+		&self,
+		key: &str,
+		size: f32,
+	) -> Element<'_, Message, iced::Theme, iced::Renderer> {
+		let path = self
+			.assets
+			.app_id
+			.as_deref()
+			.zip(self.assets.find(key))
+			.and_then(|(app_id, asset)| asset.thumbnail(app_id))
+			.filter(|path| path.exists());
+
+		match path {
+			Some(path) => image(path)
+				.width(Length::Fixed(size))
+				.height(Length::Fixed(size))
+				.into(),
+			None => container(column![])
+				.width(Length::Fixed(size))
+				.height(Length::Fixed(size))
+				.into(),
+		}
+	}
 }
 
+/// One entry of a frame or rule list, `fields` go between the label and the Up and Remove buttons
+fn rule_list_row<'a>(
+	label: String,
+	fields: impl IntoIterator<Item = Element<'a, Message, iced::Theme, iced::Renderer>>,
+	index: usize,
+	move_up: Message,
+	remove: Message,
+) -> Element<'a, Message, iced::Theme, iced::Renderer> {
+	row![text(label).width(Length::Fill)]
+		.extend(fields)
+		.push(button("Up").on_press_maybe((index > 0).then_some(move_up)))
+		.push(button("Remove").on_press(remove))
+		.spacing(10.)
+		.align_y(Vertical::Center)
+		.into()
+}
+/// The add button and on/off switch under a rule list
+fn rule_list_controls<'a>(
+	add: Message,
+	enabled: bool,
+	label: &'a str,
+	on_toggle: impl Fn(bool) -> Message + 'a,
+) -> Row<'a, Message, iced::Theme, iced::Renderer> {
+	row![
+		button("Add current activity").on_press(add),
+		toggler(enabled).label(label).on_toggle(on_toggle),
+	]
+	.spacing(10.)
+	.align_y(Vertical::Center)
+}
+fn active_label(activity: &Activity, index: usize, active: Option<usize>) -> String {
+	let label = activity.label("Rule", index);
+	if active == Some(index) {
+		format!("{label} (active)")
+	} else {
+		label
+	}
+}
 fn b(label: &str, msg: Message) -> button::Button<'_, Message, iced::Theme, iced::Renderer> {
 	button(label).on_press(msg).width(Length::Fill)
 }
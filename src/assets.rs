@@ -0,0 +1,195 @@
+use std::{
+	collections::HashMap,
+	io::ErrorKind,
+	path::{Path, PathBuf},
+};
+
+use anyhow::{Context, anyhow};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{generation::Generation, settings};
+
+const CDN_URL: &str = "https://cdn.discordapp.com/app-assets";
+const MEDIA_PROXY_URL: &str = "https://media.discordapp.net";
+const INDEX_FILE: &str = "assets.json";
+const THUMBNAIL_SIZE: u32 = 128;
+const MAX_IMAGE_SIZE: u64 = 10 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Asset {
+	pub id: String,
+	/// What goes into `large_key` and `small_key`
+	pub key: String,
+}
+
+/// Entry of the asset list the developer portal serves for an application
+#[derive(Deserialize)]
+struct PortalAsset {
+	id: String,
+	name: String,
+}
+
+/// Cached assets of the application the edited activity uses
+#[derive(Debug, Clone, Default)]
+pub struct AssetLibrary {
+	pub app_id: Option<String>,
+	pub assets: Vec<Asset>,
+	pub error: Option<String>,
+}
+
+/// Downloaded previews of image URLs, keyed by the URL
+#[derive(Debug, Default)]
+pub struct UrlThumbnails {
+	/// `None` while downloading or after it failed
+	entries: HashMap<String, Option<PathBuf>>,
+	/// Restarted on every edit, the download waits for typing to pause
+	pub generation: Generation,
+}
+
+impl Asset {
+	pub fn thumbnail(&self, app_id: &str) -> Option<PathBuf> {
+		settings::assets_dir(app_id).map(|dir| dir.join(format!("{}.png", self.id)))
+	}
+}
+
+impl AssetLibrary {
+	pub fn find(&self, key: &str) -> Option<&Asset> {
+		self.assets.iter().find(|asset| asset.key == key)
+	}
+	/// Assets whose key contains `query`, ignoring case
+	pub fn search(&self, query: &str) -> impl Iterator<Item = &Asset> {
+		let query = query.trim().to_lowercase();
+
+		self.assets
+			.iter()
+			.filter(move |asset| asset.key.to_lowercase().contains(&query))
+	}
+}
+
+impl UrlThumbnails {
+	pub fn get(&self, url: &str) -> Option<&Path> {
+		self.entries.get(url)?.as_deref()
+	}
+	/// Whether any of `urls` was never requested
+	pub fn is_missing<'a>(&self, mut urls: impl Iterator<Item = &'a str>) -> bool {
+		urls.any(|url| !self.entries.contains_key(url))
+	}
+	/// Marks the urls that were never requested as requested and returns them
+	pub fn request<'a>(&mut self, urls: impl Iterator<Item = &'a str>) -> Vec<String> {
+		let mut requested = Vec::new();
+		for url in urls {
+			if !self.entries.contains_key(url) {
+				self.entries.insert(url.to_string(), None);
+				requested.push(url.to_string());
+			}
+		}
+
+		requested
+	}
+	pub fn insert(&mut self, url: String, path: Option<PathBuf>) {
+		self.entries.insert(url, path);
+	}
+}
+
+/// Asset list cached for `app_id`, empty if nothing was imported yet
+pub async fn load(app_id: &str) -> anyhow::Result<Vec<Asset>> {
+	let Some(dir) = settings::assets_dir(app_id) else {
+		return Ok(Vec::new());
+	};
+
+	match tokio::fs::read(dir.join(INDEX_FILE)).await {
+		Ok(data) => serde_json::from_slice(&data).context("Failed to parse asset index"),
+		Err(err) if err.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+		Err(err) => Err(err.into()),
+	}
+}
+
+/// Reads an asset list exported from the developer portal and downloads every image into the cache
+pub async fn import(app_id: &str, export: &Path) -> anyhow::Result<Vec<Asset>> {
+	let dir = settings::assets_dir(app_id).ok_or(anyhow!("Invalid application id {app_id}"))?;
+	let data = tokio::fs::read(export).await?;
+	let exported: Vec<PortalAsset> =
+		serde_json::from_slice(&data).context("Failed to parse asset list")?;
+	tokio::fs::create_dir_all(&dir).await?;
+
+	let client = reqwest::Client::new();
+	let mut assets = Vec::with_capacity(exported.len());
+	// ids end up in file names
+	for asset in exported.into_iter().filter(|v| is_snowflake(&v.id)) {
+		let asset = Asset {
+			id: asset.id,
+			key: asset.name,
+		};
+		// the key is still usable without a thumbnail
+		if let Err(err) = download(&client, app_id, &asset, &dir).await {
+			warn!("Failed to download asset {}: {err}", asset.key);
+		}
+		assets.push(asset);
+	}
+	assets.sort_by(|a, b| a.key.cmp(&b.key));
+
+	tokio::fs::write(dir.join(INDEX_FILE), serde_json::to_vec_pretty(&assets)?).await?;
+
+	Ok(assets)
+}
+
+async fn download(
+	client: &reqwest::Client,
+	app_id: &str,
+	asset: &Asset,
+	dir: &Path,
+) -> anyhow::Result<()> {
+	let url = format!("{CDN_URL}/{app_id}/{}.png?size={THUMBNAIL_SIZE}", asset.id);
+	let image = client
+		.get(url)
+		.send()
+		.await?
+		.error_for_status()?
+		.bytes()
+		.await?;
+
+	tokio::fs::write(dir.join(format!("{}.png", asset.id)), image).await?;
+
+	Ok(())
+}
+
+/// Downloads the image behind `url` unless it is cached already, `mp:` links go through the media proxy
+pub async fn cache_url(url: &str) -> anyhow::Result<PathBuf> {
+	let dir = settings::image_cache_dir().ok_or(anyhow!("Unsupported platform"))?;
+	// the name has to stay the same across builds for the cache to survive updates
+	let path = dir.join(format!("{:x}", Sha256::digest(url)));
+	if tokio::fs::try_exists(&path).await? {
+		return Ok(path);
+	}
+
+	let download_url = match url.strip_prefix("mp:") {
+		Some(rest) => format!("{MEDIA_PROXY_URL}/{rest}"),
+		None => url.to_string(),
+	};
+	let mut response = reqwest::get(download_url).await?.error_for_status()?;
+	if response
+		.content_length()
+		.is_some_and(|v| v > MAX_IMAGE_SIZE)
+	{
+		return Err(anyhow!("Image is too large"));
+	}
+	// the length can be missing or wrong, so the body is counted too
+	let mut image = Vec::new();
+	while let Some(chunk) = response.chunk().await? {
+		if (image.len() + chunk.len()) as u64 > MAX_IMAGE_SIZE {
+			return Err(anyhow!("Image is too large"));
+		}
+		image.extend_from_slice(&chunk);
+	}
+	tokio::fs::create_dir_all(&dir).await?;
+	tokio::fs::write(&path, image).await?;
+
+	Ok(path)
+}
+
+/// Discord ids are plain numbers
+pub fn is_snowflake(id: &str) -> bool {
+	!id.is_empty() && id.chars().all(|c| c.is_ascii_digit())
+}
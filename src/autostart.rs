@@ -0,0 +1,141 @@
+use std::{env, io::ErrorKind, path::Path};
+
+use anyhow::Context;
+use tokio::fs;
+
+pub fn desktop_entry(exec: &Path) -> String {
+	format!(
+		"[Desktop Entry]\n\
+		Type=Application\n\
+		Name=Discord presence\n\
+		Comment=Set a custom discord rich presence\n\
+		Exec={}\n\
+		Terminal=false\n\
+		X-GNOME-Autostart-enabled=true\n",
+		escape_exec(&exec.to_string_lossy())
+	)
+}
+
+/// Writes an entry for the running executable to `file`
+pub async fn enable(file: &Path) -> anyhow::Result<()> {
+	let exec = env::current_exe().context("Failed to find the executable path")?;
+	if let Some(dir) = file.parent() {
+		fs::create_dir_all(dir).await?;
+	}
+	fs::write(file, desktop_entry(&exec))
+		.await
+		.context("Failed to write autostart entry")
+}
+
+/// Removes the entry at `file`, a missing one is fine
+pub async fn disable(file: &Path) -> anyhow::Result<()> {
+	match fs::remove_file(file).await {
+		Err(err) if err.kind() != ErrorKind::NotFound => {
+			Err(err).context("Failed to remove autostart entry")
+		}
+		_ => Ok(()),
+	}
+}
+
+// https://specifications.freedesktop.org/desktop-entry-spec/latest/exec-variables.html
+fn escape_exec(arg: &str) -> String {
+	let arg = arg.replace('%', "%%");
+	if !arg.contains(|c: char| c.is_whitespace() || "\"'\\><~|&;$*?#()`".contains(c)) {
+		return arg;
+	}
+
+	let mut quoted = String::from("\"");
+	for c in arg.chars() {
+		if matches!(c, '"' | '`' | '$' | '\\') {
+			quoted.push('\\');
+		}
+		quoted.push(c);
+	}
+	quoted.push('"');
+
+	// the value is a string too, so the backslashes of the quoting get escaped once more
+	quoted.replace('\\', "\\\\")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn plain_paths_stay_unquoted() {
+		assert_eq!(
+			escape_exec("/usr/bin/discord-presence"),
+			"/usr/bin/discord-presence"
+		);
+	}
+
+	#[test]
+	fn percent_is_doubled() {
+		assert_eq!(escape_exec("/opt/100%/app"), "/opt/100%%/app");
+	}
+
+	#[test]
+	fn spaces_get_quoted() {
+		assert_eq!(
+			escape_exec("/home/me/My Apps/app"),
+			"\"/home/me/My Apps/app\""
+		);
+	}
+
+	#[test]
+	fn reserved_characters_are_escaped_twice() {
+		assert_eq!(escape_exec("/a\"b$c"), "\"/a\\\\\"b\\\\$c\"");
+		assert_eq!(escape_exec("/a\\b"), "\"/a\\\\\\\\b\"");
+	}
+
+	#[test]
+	fn entry_has_the_autostart_keys() {
+		let entry = desktop_entry(Path::new("/opt/My Apps/discord-presence"));
+		let lines: Vec<&str> = entry.lines().collect();
+
+		assert_eq!(lines[0], "[Desktop Entry]");
+		assert!(lines.contains(&"Name=Discord presence"));
+		assert!(lines.contains(&"Exec=\"/opt/My Apps/discord-presence\""));
+		assert!(lines.contains(&"X-GNOME-Autostart-enabled=true"));
+	}
+
+	#[cfg(any(
+		target_os = "linux",
+		target_os = "dragonfly",
+		target_os = "freebsd",
+		target_os = "netbsd",
+		target_os = "openbsd"
+	))]
+	#[tokio::test]
+	async fn entry_lives_under_xdg_config_home() {
+		let dir = tempfile::tempdir().unwrap();
+		// the only test touching the environment, nothing else reads it concurrently
+		unsafe { env::set_var("XDG_CONFIG_HOME", dir.path()) };
+
+		let file = crate::settings::autostart_file().unwrap();
+		assert_eq!(file.parent(), Some(dir.path().join("autostart").as_path()));
+		assert_eq!(file.extension().and_then(|v| v.to_str()), Some("desktop"));
+
+		enable(&file).await.unwrap();
+		let exec = env::current_exe().unwrap();
+		let written = std::fs::read_to_string(&file).unwrap();
+		assert!(
+			written
+				.lines()
+				.any(|line| line == format!("Exec={}", escape_exec(&exec.to_string_lossy())))
+		);
+	}
+
+	#[tokio::test]
+	async fn disabling_removes_the_entry() {
+		let dir = tempfile::tempdir().unwrap();
+		let file = dir.path().join("app.desktop");
+
+		enable(&file).await.unwrap();
+		assert!(file.exists());
+		disable(&file).await.unwrap();
+		assert!(!file.exists());
+		// already gone
+		disable(&file).await.unwrap();
+	}
+}
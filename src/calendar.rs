@@ -0,0 +1,661 @@
+use std::{path::Path, time::Duration};
+
+use chrono::{
+	DateTime, Datelike, Days, Local, Months, NaiveDateTime, TimeDelta, TimeZone, Utc, Weekday,
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+	activity::{Activity, ImageSource, TimestampType},
+	generation::Generation,
+	template,
+};
+
+const DATE_TIME_FORMAT: &str = "%Y%m%dT%H%M%S";
+const REDACTED_TITLE: &str = "In a meeting";
+// the file is read again this often so edits show up without a restart
+const RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+// recurring events are only expanded this far around the time of loading
+const LOOK_BEHIND: Days = Days::new(1);
+const LOOK_AHEAD: Days = Days::new(8);
+
+#[derive(Error, Debug)]
+pub enum CalendarError {
+	#[error("Invalid date {0}")]
+	InvalidDate(String),
+	#[error("Invalid duration {0}")]
+	InvalidDuration(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalendarEvent {
+	pub summary: String,
+	pub start: DateTime<Utc>,
+	pub end: DateTime<Utc>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Calendar {
+	pub enabled: bool,
+	/// Local `.ics` file
+	pub path: String,
+	/// Hides event titles behind a generic text
+	pub redact: bool,
+	/// Large image key or URL while an event is in progress, keeps the edited one when empty
+	pub image_key: String,
+	#[serde(skip)]
+	pub events: Vec<CalendarEvent>,
+	#[serde(skip)]
+	pub current: Option<CalendarEvent>,
+	#[serde(skip)]
+	pub error: Option<String>,
+	#[serde(skip)]
+	pub generation: Generation,
+}
+
+#[derive(Default)]
+struct RawEvent {
+	uid: String,
+	summary: String,
+	start: Option<DateTime<Utc>>,
+	end: Option<DateTime<Utc>>,
+	duration: Option<TimeDelta>,
+	rule: Option<String>,
+	exceptions: Vec<DateTime<Utc>>,
+	recurrence_id: Option<DateTime<Utc>>,
+	all_day: bool,
+	cancelled: bool,
+}
+
+enum Frequency {
+	Daily,
+	Weekly,
+	Monthly,
+	Yearly,
+}
+
+struct Recurrence {
+	frequency: Frequency,
+	interval: u32,
+	count: Option<u32>,
+	until: Option<DateTime<Utc>>,
+	days: Vec<Weekday>,
+}
+
+impl Calendar {
+	/// The most recently started event in progress at `now`
+	pub fn current_at(&self, now: DateTime<Utc>) -> Option<&CalendarEvent> {
+		self.events
+			.iter()
+			.filter(|event| event.start <= now && now < event.end)
+			.max_by_key(|event| event.start)
+	}
+	/// The event starting next after `now`
+	pub fn upcoming(&self, now: DateTime<Utc>) -> Option<&CalendarEvent> {
+		self.events
+			.iter()
+			.filter(|event| event.start > now)
+			.min_by_key(|event| event.start)
+	}
+	/// Activity shown while `event` is in progress, keeping the application id and images of `base`
+	pub fn activity(&self, event: &CalendarEvent, base: &Activity) -> Activity {
+		let title = if self.redact || event.summary.is_empty() {
+			REDACTED_TITLE.to_string()
+		} else {
+			// summaries come from the calendar, not the user, so braces in them are literal
+			template::escape(&event.summary)
+		};
+		let (large_key, large_source) = match self.image_key.trim() {
+			"" => (base.large_key.clone(), base.large_source),
+			key => (Some(key.to_string()), ImageSource::of(key)),
+		};
+
+		Activity {
+			id: base.id.clone(),
+			details: Some(title),
+			timestamp_type: TimestampType::EndsAt,
+			start_timestamp: Some(event.start),
+			end_timestamp: Some(event.end),
+			large_key,
+			large_source,
+			small_key: base.small_key.clone(),
+			small_source: base.small_source,
+			..Default::default()
+		}
+	}
+	/// `false` for loads and timers from before a restart, and for all of them while disabled
+	pub fn is_current(&self, generation: Generation) -> bool {
+		self.enabled && self.generation.is_current(generation)
+	}
+	/// How long to sleep before the next event starts or ends, or the file is read again
+	pub fn wait(&self) -> Duration {
+		let now = Utc::now();
+
+		self.events
+			.iter()
+			.flat_map(|event| [event.start, event.end])
+			.filter(|at| *at > now)
+			.min()
+			.and_then(|at| (at - now).to_std().ok())
+			.map_or(RELOAD_INTERVAL, |v| v.min(RELOAD_INTERVAL))
+	}
+}
+
+pub async fn load(path: impl AsRef<Path>) -> anyhow::Result<Vec<CalendarEvent>> {
+	let data = tokio::fs::read_to_string(path).await?;
+
+	Ok(parse(&data, Utc::now()))
+}
+
+/// Events of an iCalendar file, recurring ones expanded around `now`
+///
+/// All day events are skipped, they would cover the whole day. So are events with invalid dates,
+/// the rest of the file still counts. Times with a `TZID` are read as local time.
+pub fn parse(data: &str, now: DateTime<Utc>) -> Vec<CalendarEvent> {
+	let mut raw = Vec::new();
+	let mut current: Option<RawEvent> = None;
+	// components nested in an event, like alarms
+	let mut depth = 0;
+
+	for line in unfold(data) {
+		let Some((name, value)) = line.split_once(':') else {
+			continue;
+		};
+		let mut params = name.split(';');
+		let name = params.next().unwrap_or_default().to_ascii_uppercase();
+		let params: Vec<&str> = params.collect();
+
+		match (name.as_str(), value) {
+			("BEGIN", "VEVENT") => {
+				current = Some(RawEvent::default());
+				depth = 0;
+			}
+			("END", "VEVENT") => raw.extend(current.take()),
+			("BEGIN", _) if current.is_some() => depth += 1,
+			("END", _) if current.is_some() => depth -= 1,
+			_ if depth > 0 => {}
+			(name, value) => {
+				let Some(event) = &mut current else {
+					continue;
+				};
+				if let Err(err) = event.set(name, &params, value) {
+					warn!("Skipping calendar event: {err}");
+					current = None;
+				}
+			}
+		}
+	}
+
+	let overridden: Vec<(String, DateTime<Utc>)> = raw
+		.iter()
+		.filter_map(|event| Some((event.uid.clone(), event.recurrence_id?)))
+		.collect();
+	let window = (
+		now.checked_sub_days(LOOK_BEHIND).unwrap_or(now),
+		now.checked_add_days(LOOK_AHEAD).unwrap_or(now),
+	);
+
+	let mut events: Vec<CalendarEvent> = raw
+		.into_iter()
+		.filter(|event| !event.all_day && !event.cancelled)
+		.flat_map(|mut event| {
+			// moved or changed occurrences are separate events with the same uid
+			event.exceptions.extend(
+				overridden
+					.iter()
+					.filter(|(uid, _)| *uid == event.uid)
+					.map(|(_, at)| *at),
+			);
+			event.occurrences(window)
+		})
+		.collect();
+	events.sort_by_key(|event| event.start);
+
+	events
+}
+
+impl RawEvent {
+	fn set(&mut self, name: &str, params: &[&str], value: &str) -> Result<(), CalendarError> {
+		match name {
+			"UID" => self.uid = value.to_string(),
+			"SUMMARY" => self.summary = unescape(value),
+			"STATUS" => self.cancelled = value.eq_ignore_ascii_case("CANCELLED"),
+			"DTSTART" => match parse_date(params, value)? {
+				Some(start) => self.start = Some(start),
+				None => self.all_day = true,
+			},
+			"DTEND" => self.end = parse_date(params, value)?,
+			"DURATION" => self.duration = Some(parse_duration(value)?),
+			"RRULE" => self.rule = Some(value.to_string()),
+			"EXDATE" => {
+				for value in value.split(',') {
+					self.exceptions.extend(parse_date(params, value)?);
+				}
+			}
+			"RECURRENCE-ID" => self.recurrence_id = parse_date(params, value)?,
+			_ => {}
+		}
+
+		Ok(())
+	}
+	fn occurrences(&self, (from, to): (DateTime<Utc>, DateTime<Utc>)) -> Vec<CalendarEvent> {
+		let Some(start) = self.start else {
+			return Vec::new();
+		};
+		let length = match (self.end, self.duration) {
+			(Some(end), _) => end - start,
+			(None, Some(duration)) => duration,
+			(None, None) => TimeDelta::zero(),
+		};
+		// an event without a length is never in progress
+		if length <= TimeDelta::zero() {
+			return Vec::new();
+		}
+
+		let starts = match self.rule.as_deref().and_then(Recurrence::parse) {
+			// a moved occurrence only stands for itself
+			Some(rule) if self.recurrence_id.is_none() => rule.starts(start, to),
+			_ => vec![start],
+		};
+
+		starts
+			.into_iter()
+			.filter(|start| !self.exceptions.contains(start))
+			.map(|start| CalendarEvent {
+				summary: self.summary.clone(),
+				start,
+				end: start + length,
+			})
+			.filter(|event| event.end > from && event.start < to)
+			.collect()
+	}
+}
+
+impl Recurrence {
+	/// `None` for rules this parser doesnt understand, the event then only happens once
+	fn parse(rule: &str) -> Option<Recurrence> {
+		let mut recurrence = Recurrence {
+			frequency: Frequency::Daily,
+			interval: 1,
+			count: None,
+			until: None,
+			days: Vec::new(),
+		};
+		let mut frequency = None;
+
+		for part in rule.split(';') {
+			let (key, value) = part.split_once('=')?;
+			match key.to_ascii_uppercase().as_str() {
+				"FREQ" => {
+					frequency = Some(match value.to_ascii_uppercase().as_str() {
+						"DAILY" => Frequency::Daily,
+						"WEEKLY" => Frequency::Weekly,
+						"MONTHLY" => Frequency::Monthly,
+						"YEARLY" => Frequency::Yearly,
+						_ => return None,
+					})
+				}
+				"INTERVAL" => recurrence.interval = value.parse().ok().filter(|v| *v > 0)?,
+				"COUNT" => recurrence.count = Some(value.parse().ok()?),
+				"UNTIL" => recurrence.until = parse_date(&[], value).ok()?,
+				"BYDAY" => {
+					recurrence.days = value.split(',').map(weekday).collect::<Option<_>>()?
+				}
+				"WKST" => {}
+				_ => return None,
+			}
+		}
+		recurrence.frequency = frequency?;
+		// only weekly rules can pick days without turning into nth weekday of the month
+		if !recurrence.days.is_empty() && !matches!(recurrence.frequency, Frequency::Weekly) {
+			return None;
+		}
+
+		Some(recurrence)
+	}
+	/// Every start from `first` up to `to`, repeated in local time so they stay put across DST
+	fn starts(&self, first: DateTime<Utc>, to: DateTime<Utc>) -> Vec<DateTime<Utc>> {
+		let first_local = first.with_timezone(&Local).naive_local();
+		let mut starts = Vec::new();
+		let mut count = 0;
+
+		for step in 0.. {
+			let Some(candidates) = self.period(first_local, step) else {
+				break;
+			};
+
+			for local in candidates.into_iter().filter(|v| *v >= first_local) {
+				let Some(start) = Local.from_local_datetime(&local).earliest() else {
+					continue;
+				};
+				let start = start.to_utc();
+				if start >= to
+					|| self.until.is_some_and(|until| start > until)
+					|| self.count.is_some_and(|max| count >= max)
+				{
+					return starts;
+				}
+				count += 1;
+				starts.push(start);
+			}
+		}
+
+		starts
+	}
+	/// Starts within the `step`th period after the one `first` is in
+	fn period(&self, first: NaiveDateTime, step: u32) -> Option<Vec<NaiveDateTime>> {
+		let offset = step.checked_mul(self.interval)?;
+
+		Some(match self.frequency {
+			Frequency::Daily => vec![first.checked_add_days(Days::new(offset.into()))?],
+			Frequency::Weekly => {
+				let week = first.checked_add_days(Days::new(u64::from(offset) * 7))?;
+				if self.days.is_empty() {
+					return Some(vec![week]);
+				}
+				let monday =
+					week.checked_sub_days(Days::new(week.weekday().num_days_from_monday().into()))?;
+				let mut days: Vec<NaiveDateTime> = self
+					.days
+					.iter()
+					.filter_map(|day| {
+						monday.checked_add_days(Days::new(day.num_days_from_monday().into()))
+					})
+					.collect();
+				days.sort();
+				days
+			}
+			Frequency::Monthly => vec![first.checked_add_months(Months::new(offset))?],
+			Frequency::Yearly => {
+				vec![first.checked_add_months(Months::new(offset.checked_mul(12)?))?]
+			}
+		})
+	}
+}
+
+fn weekday(code: &str) -> Option<Weekday> {
+	Some(match code.to_ascii_uppercase().as_str() {
+		"MO" => Weekday::Mon,
+		"TU" => Weekday::Tue,
+		"WE" => Weekday::Wed,
+		"TH" => Weekday::Thu,
+		"FR" => Weekday::Fri,
+		"SA" => Weekday::Sat,
+		"SU" => Weekday::Sun,
+		_ => return None,
+	})
+}
+
+/// Joins lines continued with leading whitespace
+fn unfold(data: &str) -> Vec<String> {
+	let mut lines: Vec<String> = Vec::new();
+
+	for line in data.lines() {
+		match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+			(Some(rest), Some(last)) => last.push_str(rest),
+			_ => lines.push(line.trim_end().to_string()),
+		}
+	}
+
+	lines
+}
+
+fn unescape(value: &str) -> String {
+	let mut result = String::with_capacity(value.len());
+	let mut chars = value.chars();
+
+	while let Some(c) = chars.next() {
+		if c != '\\' {
+			result.push(c);
+			continue;
+		}
+		match chars.next() {
+			Some('n' | 'N') => result.push('\n'),
+			Some(c) => result.push(c),
+			None => break,
+		}
+	}
+
+	result
+}
+
+/// `None` for plain dates, those mark all day events
+fn parse_date(params: &[&str], value: &str) -> Result<Option<DateTime<Utc>>, CalendarError> {
+	let invalid = || CalendarError::InvalidDate(value.to_string());
+	let is_date = params
+		.iter()
+		.any(|param| param.eq_ignore_ascii_case("VALUE=DATE"))
+		|| value.len() == 8;
+	if is_date {
+		return Ok(None);
+	}
+
+	match value.strip_suffix('Z') {
+		Some(utc) => NaiveDateTime::parse_from_str(utc, DATE_TIME_FORMAT)
+			.map(|v| Some(v.and_utc()))
+			.map_err(|_| invalid()),
+		None => {
+			let local =
+				NaiveDateTime::parse_from_str(value, DATE_TIME_FORMAT).map_err(|_| invalid())?;
+			let local = Local
+				.from_local_datetime(&local)
+				.earliest()
+				.ok_or_else(invalid)?;
+			Ok(Some(local.to_utc()))
+		}
+	}
+}
+
+/// `P1DT2H30M`, `PT15M`, `P1W` and the like
+fn parse_duration(value: &str) -> Result<TimeDelta, CalendarError> {
+	let invalid = || CalendarError::InvalidDuration(value.to_string());
+	let (sign, rest) = match value.strip_prefix('-') {
+		Some(rest) => (-1, rest),
+		None => (1, value.strip_prefix('+').unwrap_or(value)),
+	};
+	let rest = rest.strip_prefix('P').ok_or_else(invalid)?;
+	let mut seconds = 0i64;
+	let mut number = String::new();
+
+	for c in rest.chars() {
+		let unit = match c {
+			'0'..='9' => {
+				number.push(c);
+				continue;
+			}
+			'T' => continue,
+			'W' => 7 * 24 * 3600,
+			'D' => 24 * 3600,
+			'H' => 3600,
+			'M' => 60,
+			'S' => 1,
+			_ => return Err(invalid()),
+		};
+		let amount: i64 = number.parse().map_err(|_| invalid())?;
+		seconds += amount * unit;
+		number.clear();
+	}
+	if !number.is_empty() {
+		return Err(invalid());
+	}
+
+	Ok(TimeDelta::seconds(sign * seconds))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// a wednesday, events are at noon UTC so their weekday is the same in most time zones
+	fn now() -> DateTime<Utc> {
+		Utc.with_ymd_and_hms(2026, 6, 10, 12, 0, 0).unwrap()
+	}
+
+	fn at(day: u32, hour: u32) -> DateTime<Utc> {
+		Utc.with_ymd_and_hms(2026, 6, day, hour, 0, 0).unwrap()
+	}
+
+	fn calendar(events: &[&str]) -> String {
+		let mut data = String::from("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n");
+		for event in events {
+			data.push_str("BEGIN:VEVENT\r\n");
+			data.push_str(event);
+			data.push_str("END:VEVENT\r\n");
+		}
+		data.push_str("END:VCALENDAR\r\n");
+		data
+	}
+
+	fn starts(events: &[CalendarEvent]) -> Vec<DateTime<Utc>> {
+		events.iter().map(|event| event.start).collect()
+	}
+
+	#[test]
+	fn single_event() {
+		let data = calendar(&[
+			"UID:a\r\nSUMMARY:Standup\r\nDTSTART:20260610T120000Z\r\nDTEND:20260610T123000Z\r\n",
+		]);
+
+		assert_eq!(
+			parse(&data, now()),
+			[CalendarEvent {
+				summary: String::from("Standup"),
+				start: at(10, 12),
+				end: at(10, 12) + TimeDelta::minutes(30),
+			}]
+		);
+	}
+
+	#[test]
+	fn duration_instead_of_end() {
+		let data = calendar(&["UID:a\r\nDTSTART:20260610T120000Z\r\nDURATION:PT1H30M\r\n"]);
+
+		let events = parse(&data, now());
+		assert_eq!(events[0].end, at(10, 13) + TimeDelta::minutes(30));
+	}
+
+	#[test]
+	fn weekly_by_day() {
+		// starts on the monday before, only occurrences around now are expanded
+		let data = calendar(&[
+			"UID:a\r\nDTSTART:20260601T120000Z\r\nDTEND:20260601T130000Z\r\nRRULE:FREQ=WEEKLY;BYDAY=MO,WE,FR\r\n",
+		]);
+
+		assert_eq!(
+			starts(&parse(&data, now())),
+			[at(10, 12), at(12, 12), at(15, 12), at(17, 12)]
+		);
+	}
+
+	#[test]
+	fn count_and_until() {
+		let data = calendar(&[
+			"UID:a\r\nDTSTART:20260609T120000Z\r\nDTEND:20260609T130000Z\r\nRRULE:FREQ=DAILY;COUNT=3\r\n",
+			"UID:b\r\nDTSTART:20260610T150000Z\r\nDTEND:20260610T160000Z\r\nRRULE:FREQ=DAILY;UNTIL=20260612T150000Z\r\n",
+		]);
+
+		assert_eq!(
+			starts(&parse(&data, now())),
+			[
+				at(9, 12),
+				at(10, 12),
+				at(10, 15),
+				at(11, 12),
+				at(11, 15),
+				at(12, 15)
+			]
+		);
+	}
+
+	#[test]
+	fn exdate_is_left_out() {
+		let data = calendar(&[
+			"UID:a\r\nDTSTART:20260610T120000Z\r\nDTEND:20260610T130000Z\r\nRRULE:FREQ=DAILY;COUNT=3\r\nEXDATE:20260611T120000Z\r\n",
+		]);
+
+		assert_eq!(starts(&parse(&data, now())), [at(10, 12), at(12, 12)]);
+	}
+
+	#[test]
+	fn moved_occurrence_replaces_the_original() {
+		let data = calendar(&[
+			"UID:a\r\nSUMMARY:Sync\r\nDTSTART:20260610T120000Z\r\nDTEND:20260610T130000Z\r\nRRULE:FREQ=DAILY;COUNT=3\r\n",
+			"UID:a\r\nSUMMARY:Sync (moved)\r\nRECURRENCE-ID:20260611T120000Z\r\nDTSTART:20260611T150000Z\r\nDTEND:20260611T160000Z\r\n",
+		]);
+
+		let events = parse(&data, now());
+		assert_eq!(starts(&events), [at(10, 12), at(11, 15), at(12, 12)]);
+		assert_eq!(events[1].summary, "Sync (moved)");
+	}
+
+	#[test]
+	fn folded_and_escaped_summary() {
+		let data = calendar(&[
+			"UID:a\r\nSUMMARY:Weekly plan\r\n ning\\, notes\\nand more\r\nDTSTART:20260610T120000Z\r\nDTEND:20260610T130000Z\r\n",
+		]);
+
+		assert_eq!(
+			parse(&data, now())[0].summary,
+			"Weekly planning, notes\nand more"
+		);
+	}
+
+	#[test]
+	fn all_day_and_cancelled_events_are_skipped() {
+		let data = calendar(&[
+			"UID:a\r\nDTSTART;VALUE=DATE:20260610\r\nDTEND;VALUE=DATE:20260611\r\n",
+			"UID:b\r\nDTSTART:20260610T120000Z\r\nDTEND:20260610T130000Z\r\nSTATUS:CANCELLED\r\n",
+		]);
+
+		assert!(parse(&data, now()).is_empty());
+	}
+
+	#[test]
+	fn nested_components_are_ignored() {
+		let data = calendar(&[
+			"UID:a\r\nDTSTART:20260610T120000Z\r\nBEGIN:VALARM\r\nDURATION:garbage\r\nEND:VALARM\r\nDTEND:20260610T130000Z\r\n",
+		]);
+
+		assert_eq!(starts(&parse(&data, now())), [at(10, 12)]);
+	}
+
+	#[test]
+	fn invalid_event_only_skips_itself() {
+		let data = calendar(&[
+			"UID:a\r\nDTSTART:2026-06-10 12:00\r\nDTEND:20260610T130000Z\r\n",
+			"UID:b\r\nDTSTART:20260610T120000Z\r\nDTEND:20260610T130000Z\r\n",
+		]);
+
+		assert_eq!(starts(&parse(&data, now())), [at(10, 12)]);
+	}
+
+	#[test]
+	fn durations() {
+		assert_eq!(parse_duration("PT15M").unwrap(), TimeDelta::minutes(15));
+		assert_eq!(
+			parse_duration("P1DT2H30M").unwrap(),
+			TimeDelta::hours(26) + TimeDelta::minutes(30)
+		);
+		assert_eq!(parse_duration("P1W").unwrap(), TimeDelta::days(7));
+		assert_eq!(parse_duration("-PT5M").unwrap(), TimeDelta::minutes(-5));
+		assert!(parse_duration("15M").is_err());
+		assert!(parse_duration("PT15").is_err());
+	}
+
+	#[test]
+	fn summary_braces_are_escaped() {
+		let event = CalendarEvent {
+			summary: String::from("Review {time} }{"),
+			start: at(10, 12),
+			end: at(10, 13),
+		};
+		let activity = Calendar::default().activity(&event, &Activity::default());
+
+		assert_eq!(activity.details.as_deref(), Some("Review {{time}} }}{{"));
+		assert_eq!(activity.start_timestamp, Some(at(10, 12)));
+		assert_eq!(activity.end_timestamp, Some(at(10, 13)));
+	}
+}
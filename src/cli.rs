@@ -0,0 +1,198 @@
+use std::path::PathBuf;
+
+use iced::futures::{
+	SinkExt,
+	channel::mpsc::{self, UnboundedReceiver, UnboundedSender},
+};
+
+#[cfg(unix)]
+use crate::control::{self, ControlRequest};
+use crate::{
+	activity::Activity,
+	app::message::MainThreadMessage,
+	presence::{Presence, PresenceError, PresenceThreadMessage},
+	settings::SettingsFile,
+};
+
+const USAGE: &str = "Usage: discord-presence [set <file.crp> | clear | status]
+       discord-presence ctl [get | set <file.crp> | patch <json> | clear | connect | disconnect]";
+
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+pub enum Command {
+	Set(PathBuf),
+	Clear,
+	Status,
+	#[cfg(unix)]
+	Control(ControlCommand),
+}
+
+#[cfg(unix)]
+pub enum ControlCommand {
+	Request(ControlRequest),
+	Set(PathBuf),
+	Patch(String),
+}
+
+struct Headless {
+	presence: Presence,
+	send: UnboundedSender<MainThreadMessage>,
+	// kept alive so the presence replies dont fail to send
+	_recv: UnboundedReceiver<PresenceThreadMessage>,
+}
+
+impl Command {
+	pub fn parse(args: &[String]) -> Option<Command> {
+		match args {
+			[cmd, path] if cmd == "set" => Some(Command::Set(PathBuf::from(path))),
+			[cmd] if cmd == "clear" => Some(Command::Clear),
+			[cmd] if cmd == "status" => Some(Command::Status),
+			#[cfg(unix)]
+			[cmd, sub] if cmd == "ctl" => ControlRequest::parse(sub)
+				.map(|request| Command::Control(ControlCommand::Request(request))),
+			#[cfg(unix)]
+			[cmd, sub, path] if cmd == "ctl" && sub == "set" => {
+				Some(Command::Control(ControlCommand::Set(PathBuf::from(path))))
+			}
+			#[cfg(unix)]
+			[cmd, sub, patch] if cmd == "ctl" && sub == "patch" => {
+				Some(Command::Control(ControlCommand::Patch(patch.clone())))
+			}
+			_ => None,
+		}
+	}
+
+	async fn execute(self) -> anyhow::Result<()> {
+		let mut headless = Headless::new();
+
+		match self {
+			Command::Set(path) => {
+				let activity = Activity::read(path).await?;
+				let Some(id) = activity.id.clone() else {
+					return Err(PresenceError::NoIdError.into());
+				};
+
+				headless.dispatch(MainThreadMessage::Connect(id)).await?;
+				headless
+					.dispatch(MainThreadMessage::SetActivity(activity))
+					.await?;
+
+				println!("Presence set, press Ctrl+C to clear it");
+				tokio::signal::ctrl_c().await?;
+
+				headless.dispatch(MainThreadMessage::Disconnect).await?;
+			}
+			// discord keeps an activity per connection, so only the running app can clear its own
+			#[cfg(unix)]
+			Command::Clear => {
+				control_request(&ControlRequest::Clear).await?;
+
+				println!("Presence cleared");
+			}
+			#[cfg(not(unix))]
+			Command::Clear => {
+				return Err(anyhow::anyhow!(
+					"Clearing needs the control socket of the running app, which is unix only"
+				));
+			}
+			Command::Status => {
+				headless
+					.dispatch(MainThreadMessage::Connect(saved_id()?))
+					.await?;
+				headless.dispatch(MainThreadMessage::Disconnect).await?;
+
+				println!("Discord is running");
+			}
+			#[cfg(unix)]
+			Command::Control(command) => send_control(command).await?,
+		}
+
+		Ok(())
+	}
+}
+
+impl Headless {
+	fn new() -> Self {
+		let (send, presence_recv) = mpsc::unbounded::<MainThreadMessage>();
+		let (presence_send, recv) = mpsc::unbounded::<PresenceThreadMessage>();
+
+		Headless {
+			presence: Presence::new(presence_send, presence_recv),
+			send,
+			_recv: recv,
+		}
+	}
+	async fn dispatch(&mut self, msg: MainThreadMessage) -> anyhow::Result<()> {
+		self.send.send(msg).await?;
+		self.presence.event_loop().await
+	}
+}
+
+#[cfg(unix)]
+async fn send_control(command: ControlCommand) -> anyhow::Result<()> {
+	use anyhow::Context;
+
+	let request = match command {
+		ControlCommand::Request(request) => request,
+		ControlCommand::Set(path) => ControlRequest::Set {
+			activity: Activity::read(path).await?,
+		},
+		ControlCommand::Patch(patch) => ControlRequest::Patch {
+			activity: serde_json::from_str(&patch).context("Patch must be a json object")?,
+		},
+	};
+
+	let response = control_request(&request).await?;
+	println!("{}", serde_json::to_string_pretty(&response)?);
+
+	Ok(())
+}
+
+/// Sends `request` to the running app, failing when it is not running or refused the request
+#[cfg(unix)]
+async fn control_request(request: &ControlRequest) -> anyhow::Result<control::ControlResponse> {
+	use anyhow::{Context, anyhow};
+
+	let path = control::socket_path()?;
+	let response = control::request(&path, request)
+		.await
+		.with_context(|| format!("Failed to reach the running app at {}", path.display()))?;
+	if let Some(err) = &response.error {
+		return Err(anyhow!(err.clone()));
+	}
+
+	Ok(response)
+}
+
+fn saved_id() -> anyhow::Result<String> {
+	SettingsFile::read()?
+		.activity
+		.id
+		.ok_or(PresenceError::NoIdError.into())
+}
+
+pub fn run(args: &[String]) -> i32 {
+	let Some(command) = Command::parse(args) else {
+		eprintln!("{USAGE}");
+		return EXIT_USAGE;
+	};
+
+	let runtime = match tokio::runtime::Runtime::new() {
+		Ok(runtime) => runtime,
+		Err(err) => {
+			eprintln!("{err}");
+			return EXIT_FAILURE;
+		}
+	};
+
+	let Err(err) = runtime.block_on(command.execute()) else {
+		return 0;
+	};
+
+	eprintln!("{err:#}");
+	match err.downcast_ref::<PresenceError>() {
+		Some(err) => err.exit_code(),
+		None => EXIT_FAILURE,
+	}
+}
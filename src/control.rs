@@ -0,0 +1,230 @@
+use anyhow::anyhow;
+use iced::futures::{
+	SinkExt, StreamExt,
+	channel::mpsc::{self, UnboundedSender},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{activity::Activity, app::ConnectionState};
+
+#[cfg(unix)]
+pub use unix::{Control, request, socket_path};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "lowercase")]
+#[allow(clippy::large_enum_variant)]
+pub enum ControlRequest {
+	Set { activity: Activity },
+	Patch { activity: Map<String, Value> },
+	Clear,
+	Connect,
+	Disconnect,
+	Get,
+}
+
+/// Replies to commands that change something only confirm they were accepted, the change is
+/// still on its way to discord so `connection` is left out. `get` reports the state afterwards
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ControlResponse {
+	pub ok: bool,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub activity: Option<Activity>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub connection: Option<ConnectionState>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ControlMessage {
+	pub request: ControlRequest,
+	pub reply: UnboundedSender<ControlResponse>,
+}
+
+impl ControlRequest {
+	pub fn parse(command: &str) -> Option<ControlRequest> {
+		match command {
+			"clear" => Some(ControlRequest::Clear),
+			"connect" => Some(ControlRequest::Connect),
+			"disconnect" => Some(ControlRequest::Disconnect),
+			"get" => Some(ControlRequest::Get),
+			_ => None,
+		}
+	}
+}
+
+impl ControlResponse {
+	pub fn state(activity: &Activity, connection: &ConnectionState) -> Self {
+		ControlResponse {
+			ok: true,
+			error: None,
+			activity: Some(activity.clone()),
+			connection: Some(connection.clone()),
+		}
+	}
+	/// The edited activity is already updated, the connection might still change
+	pub fn accepted(activity: &Activity) -> Self {
+		ControlResponse {
+			ok: true,
+			error: None,
+			activity: Some(activity.clone()),
+			connection: None,
+		}
+	}
+	pub fn error(err: impl ToString) -> Self {
+		ControlResponse {
+			ok: false,
+			error: Some(err.to_string()),
+			activity: None,
+			connection: None,
+		}
+	}
+}
+
+/// Forwards `request` to the app and waits for its reply
+pub async fn dispatch(
+	send: &mut UnboundedSender<ControlMessage>,
+	request: ControlRequest,
+) -> anyhow::Result<ControlResponse> {
+	let (reply, mut recv) = mpsc::unbounded::<ControlResponse>();
+	send.send(ControlMessage { request, reply }).await?;
+
+	recv.next()
+		.await
+		.ok_or(anyhow!("App closed before replying"))
+}
+
+pub fn patch_activity(activity: &Activity, patch: &Map<String, Value>) -> anyhow::Result<Activity> {
+	let mut value = serde_json::to_value(activity)?;
+	if let Value::Object(fields) = &mut value {
+		fields.extend(patch.clone());
+	}
+
+	Ok(serde_json::from_value(value)?)
+}
+
+#[cfg(unix)]
+mod unix {
+	use std::{
+		env,
+		fs::Permissions,
+		os::unix::fs::PermissionsExt,
+		path::{Path, PathBuf},
+	};
+
+	use anyhow::anyhow;
+	use iced::futures::channel::mpsc::UnboundedSender;
+	use log::error;
+	use tokio::{
+		io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+		net::{UnixListener, UnixStream},
+		task,
+	};
+
+	use super::{ControlMessage, ControlRequest, ControlResponse, dispatch};
+
+	const SOCKET_NAME: &str = "discord-presence.sock";
+	/// Read and write for the owner only
+	const SOCKET_MODE: u32 = 0o600;
+
+	pub struct Control {
+		send: UnboundedSender<ControlMessage>,
+	}
+
+	impl Control {
+		pub fn spawn_thread(send: UnboundedSender<ControlMessage>) {
+			task::spawn(async move {
+				let listener = match bind().await {
+					Ok(listener) => listener,
+					Err(err) => {
+						error!("Failed to start control socket: {err}");
+						return;
+					}
+				};
+
+				loop {
+					let stream = match listener.accept().await {
+						Ok((stream, _)) => stream,
+						Err(err) => {
+							error!("{err}");
+							continue;
+						}
+					};
+
+					let state = Control { send: send.clone() };
+					task::spawn(async move {
+						if let Err(err) = state.event_loop(stream).await {
+							error!("{err}");
+						}
+					});
+				}
+			});
+		}
+
+		async fn event_loop(mut self, stream: UnixStream) -> anyhow::Result<()> {
+			let (read, mut write) = stream.into_split();
+			let mut lines = BufReader::new(read).lines();
+
+			while let Some(line) = lines.next_line().await? {
+				if line.trim().is_empty() {
+					continue;
+				}
+
+				let response = match serde_json::from_str::<ControlRequest>(&line) {
+					Ok(request) => dispatch(&mut self.send, request).await?,
+					Err(err) => ControlResponse::error(err),
+				};
+
+				let mut data = serde_json::to_vec(&response)?;
+				data.push(b'\n');
+				write.write_all(&data).await?;
+			}
+
+			Ok(())
+		}
+	}
+
+	/// Only the runtime dir is private to the user, a shared dir like /tmp would let anyone in
+	pub fn socket_path() -> anyhow::Result<PathBuf> {
+		env::var_os("XDG_RUNTIME_DIR")
+			.filter(|v| !v.is_empty())
+			.map(|dir| PathBuf::from(dir).join(SOCKET_NAME))
+			.ok_or(anyhow!("XDG_RUNTIME_DIR is not set"))
+	}
+
+	async fn bind() -> anyhow::Result<UnixListener> {
+		let path = socket_path()?;
+		if path.exists() {
+			if UnixStream::connect(&path).await.is_ok() {
+				return Err(anyhow!(
+					"Another instance is already listening on {}",
+					path.display()
+				));
+			}
+			tokio::fs::remove_file(&path).await?;
+		}
+
+		let listener = UnixListener::bind(&path)?;
+		tokio::fs::set_permissions(&path, Permissions::from_mode(SOCKET_MODE)).await?;
+
+		Ok(listener)
+	}
+
+	pub async fn request(path: &Path, request: &ControlRequest) -> anyhow::Result<ControlResponse> {
+		let stream = UnixStream::connect(path).await?;
+		let (read, mut write) = stream.into_split();
+
+		let mut data = serde_json::to_vec(request)?;
+		data.push(b'\n');
+		write.write_all(&data).await?;
+
+		let line = BufReader::new(read)
+			.lines()
+			.next_line()
+			.await?
+			.ok_or(anyhow!("Control socket closed without replying"))?;
+
+		Ok(serde_json::from_str(&line)?)
+	}
+}
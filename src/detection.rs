@@ -0,0 +1,147 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::activity::Activity;
+
+#[cfg(target_os = "linux")]
+pub use linux::ProcessWatcher;
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ProcessRule {
+	/// Comma separated process names, any of them running activates the rule
+	pub processes: String,
+	pub activity: Activity,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct ProcessRules {
+	#[serde(default)]
+	pub enabled: bool,
+	#[serde(default)]
+	pub rules: Vec<ProcessRule>,
+	#[serde(skip)]
+	pub active: Option<usize>,
+	#[serde(skip)]
+	pub running: BTreeSet<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RunningProcesses(pub BTreeSet<String>);
+
+impl ProcessRule {
+	pub fn new(activity: Activity) -> Self {
+		ProcessRule {
+			processes: String::new(),
+			activity,
+		}
+	}
+	pub fn names(&self) -> impl Iterator<Item = &str> {
+		self.processes
+			.split(',')
+			.map(str::trim)
+			.filter(|v| !v.is_empty())
+	}
+	pub fn matches(&self, running: &BTreeSet<String>) -> bool {
+		self.names().any(|name| running.contains(name))
+	}
+}
+
+impl ProcessRules {
+	/// Index of the first matching rule, rules higher in the list win
+	pub fn find(&self) -> Option<usize> {
+		if !self.enabled {
+			return None;
+		}
+		self.rules
+			.iter()
+			.position(|rule| rule.matches(&self.running))
+	}
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+	use std::{collections::BTreeSet, time::Duration};
+
+	use iced::futures::{SinkExt, channel::mpsc::UnboundedSender};
+	use log::error;
+	use tokio::{fs, task, time};
+
+	use super::RunningProcesses;
+	use crate::gate::Gate;
+
+	const SCAN_INTERVAL: Duration = Duration::from_secs(5);
+
+	pub struct ProcessWatcher {
+		send: UnboundedSender<RunningProcesses>,
+		gate: Gate,
+		last: BTreeSet<String>,
+	}
+
+	impl ProcessWatcher {
+		/// Scans `/proc` while `gate` is open
+		pub fn spawn_thread(send: UnboundedSender<RunningProcesses>, gate: Gate) {
+			task::spawn(async move {
+				let mut state = ProcessWatcher {
+					send,
+					gate,
+					last: BTreeSet::new(),
+				};
+
+				loop {
+					if !state.gate.is_open() {
+						state.last.clear();
+					} else if let Err(err) = state.event_loop().await {
+						error!("{err}");
+					}
+					if state.send.is_closed() {
+						return;
+					}
+					time::sleep(SCAN_INTERVAL).await;
+				}
+			});
+		}
+		async fn event_loop(&mut self) -> anyhow::Result<()> {
+			let running = scan().await?;
+			if running == self.last {
+				return Ok(());
+			}
+			self.last = running.clone();
+			self.send.send(RunningProcesses(running)).await?;
+
+			Ok(())
+		}
+	}
+
+	/// Names of every running process, both the kernel `comm` name and the executable file name
+	async fn scan() -> anyhow::Result<BTreeSet<String>> {
+		let mut names = BTreeSet::new();
+		let mut entries = fs::read_dir("/proc").await?;
+
+		while let Some(entry) = entries.next_entry().await? {
+			let file_name = entry.file_name();
+			if !file_name
+				.to_string_lossy()
+				.bytes()
+				.all(|b| b.is_ascii_digit())
+			{
+				continue;
+			}
+			let path = entry.path();
+
+			// processes can exit while scanning, so read errors are skipped
+			if let Ok(comm) = fs::read_to_string(path.join("comm")).await {
+				names.insert(comm.trim_end().to_string());
+			}
+			if let Ok(cmdline) = fs::read(path.join("cmdline")).await {
+				let exe = cmdline.split(|b| *b == 0).next().unwrap_or_default();
+				let exe = String::from_utf8_lossy(exe);
+				if let Some(name) = exe.rsplit('/').next().filter(|v| !v.is_empty()) {
+					names.insert(name.to_string());
+				}
+			}
+		}
+
+		Ok(names)
+	}
+}
@@ -0,0 +1,377 @@
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+
+use crate::{activity::Activity, template};
+
+#[cfg(target_os = "linux")]
+pub use linux::FocusWatcher;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FocusedWindow {
+	pub class: String,
+	pub title: String,
+}
+
+/// Maps the focused window onto `details`/`state`.
+/// `class` and `title` are regexes, an empty one matches anything.
+/// The outputs can reference title captures with `$1` or `${name}`
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct TitleRule {
+	pub class: String,
+	pub title: String,
+	pub details: String,
+	pub state: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct TitleRules {
+	#[serde(default)]
+	pub enabled: bool,
+	#[serde(default)]
+	pub rules: Vec<TitleRule>,
+	#[serde(skip)]
+	pub window: Option<FocusedWindow>,
+	#[serde(skip)]
+	pub applied: Option<Activity>,
+}
+
+impl TitleRule {
+	pub fn validate(&self) -> Result<(), regex::Error> {
+		Regex::new(&self.class)?;
+		Regex::new(&self.title)?;
+
+		Ok(())
+	}
+	/// `base` with details and state filled from the window, `None` if the rule doesnt match
+	pub fn apply(
+		&self,
+		window: &FocusedWindow,
+		base: &Activity,
+	) -> Result<Option<Activity>, regex::Error> {
+		if !Regex::new(&self.class)?.is_match(&window.class) {
+			return Ok(None);
+		}
+		let Some(captures) = Regex::new(&self.title)?.captures(&window.title) else {
+			return Ok(None);
+		};
+
+		let fill = |output: &str| Some(expand(output, &captures)).filter(|v| !v.is_empty());
+		let mut activity = base.clone();
+		if !self.details.is_empty() {
+			activity.details = fill(&self.details);
+		}
+		if !self.state.is_empty() {
+			activity.state = fill(&self.state);
+		}
+
+		Ok(Some(activity))
+	}
+}
+
+impl TitleRules {
+	/// Activity for the first matching rule, invalid rules are skipped
+	pub fn find(&self, base: &Activity) -> Option<Activity> {
+		if !self.enabled {
+			return None;
+		}
+		let window = self.window.as_ref()?;
+
+		self.rules
+			.iter()
+			.find_map(|rule| rule.apply(window, base).ok().flatten())
+	}
+}
+
+/// `Captures::expand`, with the captured text escaped since the outputs are templates too
+fn expand(output: &str, captures: &Captures) -> String {
+	let mut out = String::with_capacity(output.len());
+	let mut rest = output;
+
+	while let Some(i) = rest.find('$') {
+		out.push_str(&rest[..i]);
+		rest = &rest[i + 1..];
+
+		if let Some(after) = rest.strip_prefix('$') {
+			out.push('$');
+			rest = after;
+			continue;
+		}
+		let (name, after) = match rest.strip_prefix('{').and_then(|v| v.split_once('}')) {
+			Some(braced) => braced,
+			None => {
+				let end = rest
+					.find(|c: char| c != '_' && !c.is_ascii_alphanumeric())
+					.unwrap_or(rest.len());
+				if end == 0 {
+					out.push('$');
+					continue;
+				}
+				rest.split_at(end)
+			}
+		};
+
+		let group = match name.parse::<usize>() {
+			Ok(i) => captures.get(i),
+			Err(_) => captures.name(name),
+		};
+		out.push_str(&template::escape(group.map_or("", |v| v.as_str())));
+		rest = after;
+	}
+	out.push_str(rest);
+
+	out
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+	use std::{
+		env,
+		time::{Duration, Instant},
+	};
+
+	use anyhow::anyhow;
+	use iced::futures::{SinkExt, channel::mpsc::UnboundedSender};
+	use log::{error, warn};
+	use serde_json::Value;
+	use tokio::{process::Command, task, time};
+
+	use super::FocusedWindow;
+	use crate::gate::Gate;
+
+	const POLL_INTERVAL: Duration = Duration::from_secs(2);
+	// window switches are frequent, dont forward them faster than discord can show them
+	const THROTTLE: Duration = Duration::from_secs(15);
+
+	#[derive(Debug, Clone, Copy)]
+	enum Backend {
+		Hyprland,
+		Sway,
+		X11,
+	}
+
+	pub struct FocusWatcher {
+		send: UnboundedSender<FocusedWindow>,
+		gate: Gate,
+		backend: Backend,
+		sent: Option<FocusedWindow>,
+		last_send: Option<Instant>,
+	}
+
+	impl FocusWatcher {
+		/// Polls the focused window while `gate` is open
+		pub fn spawn_thread(send: UnboundedSender<FocusedWindow>, gate: Gate) {
+			let Some(backend) = Backend::detect() else {
+				warn!("Focused window detection is not supported on this desktop");
+				return;
+			};
+
+			task::spawn(async move {
+				let mut state = FocusWatcher {
+					send,
+					gate,
+					backend,
+					sent: None,
+					last_send: None,
+				};
+
+				loop {
+					if !state.gate.is_open() {
+						// whatever is focused gets sent once the rules are back
+						state.sent = None;
+						state.last_send = None;
+					} else if let Err(err) = state.event_loop().await {
+						error!("{err}");
+					}
+					if state.send.is_closed() {
+						return;
+					}
+					time::sleep(POLL_INTERVAL).await;
+				}
+			});
+		}
+		async fn event_loop(&mut self) -> anyhow::Result<()> {
+			let window = self.backend.focused().await?;
+			if self.sent.as_ref() == Some(&window)
+				|| self.last_send.is_some_and(|v| v.elapsed() < THROTTLE)
+			{
+				return Ok(());
+			}
+
+			self.sent = Some(window.clone());
+			self.last_send = Some(Instant::now());
+			self.send.send(window).await?;
+
+			Ok(())
+		}
+	}
+
+	impl Backend {
+		fn detect() -> Option<Self> {
+			if env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+				Some(Backend::Hyprland)
+			} else if env::var_os("SWAYSOCK").is_some() {
+				Some(Backend::Sway)
+			} else if env::var_os("WAYLAND_DISPLAY").is_none() && env::var_os("DISPLAY").is_some() {
+				Some(Backend::X11)
+			} else {
+				None
+			}
+		}
+		async fn focused(self) -> anyhow::Result<FocusedWindow> {
+			match self {
+				Backend::Hyprland => {
+					let json: Value =
+						serde_json::from_str(&run("hyprctl", &["activewindow", "-j"]).await?)?;
+
+					Ok(FocusedWindow {
+						class: json_str(&json, "class"),
+						title: json_str(&json, "title"),
+					})
+				}
+				Backend::Sway => {
+					let tree: Value =
+						serde_json::from_str(&run("swaymsg", &["-t", "get_tree"]).await?)?;
+					let Some(node) = find_focused(&tree) else {
+						return Ok(FocusedWindow::default());
+					};
+					let class = match node.get("app_id").and_then(Value::as_str) {
+						Some(app_id) => app_id.to_string(),
+						None => node
+							.get("window_properties")
+							.map(|v| json_str(v, "class"))
+							.unwrap_or_default(),
+					};
+
+					Ok(FocusedWindow {
+						class,
+						title: json_str(node, "name"),
+					})
+				}
+				Backend::X11 => {
+					let root = run("xprop", &["-root", "_NET_ACTIVE_WINDOW"]).await?;
+					let Some(id) = root.split_whitespace().last().filter(|v| *v != "0x0") else {
+						return Ok(FocusedWindow::default());
+					};
+					let props = run("xprop", &["-id", id, "WM_CLASS", "_NET_WM_NAME"]).await?;
+
+					let mut window = FocusedWindow::default();
+					for line in props.lines() {
+						let Some((name, value)) = line.split_once(" = ") else {
+							continue;
+						};
+						if name.starts_with("WM_CLASS") {
+							// instance, class
+							window.class =
+								value.rsplit(", ").next().map(unquote).unwrap_or_default();
+						} else if name.starts_with("_NET_WM_NAME") {
+							window.title = unquote(value);
+						}
+					}
+
+					Ok(window)
+				}
+			}
+		}
+	}
+
+	async fn run(program: &str, args: &[&str]) -> anyhow::Result<String> {
+		let output = Command::new(program).args(args).output().await?;
+		if !output.status.success() {
+			return Err(anyhow!(
+				"{program} failed: {}",
+				String::from_utf8_lossy(&output.stderr).trim()
+			));
+		}
+
+		Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+	}
+
+	fn json_str(value: &Value, key: &str) -> String {
+		value
+			.get(key)
+			.and_then(Value::as_str)
+			.unwrap_or_default()
+			.to_string()
+	}
+
+	fn find_focused(node: &Value) -> Option<&Value> {
+		if node.get("focused").and_then(Value::as_bool) == Some(true) {
+			return Some(node);
+		}
+
+		["nodes", "floating_nodes"]
+			.into_iter()
+			.filter_map(|key| node.get(key).and_then(Value::as_array))
+			.flatten()
+			.find_map(find_focused)
+	}
+
+	fn unquote(value: &str) -> String {
+		let value = value.trim();
+		let value = value
+			.strip_prefix('"')
+			.and_then(|v| v.strip_suffix('"'))
+			.unwrap_or(value);
+
+		value.replace("\\\"", "\"").replace("\\\\", "\\")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn window(title: &str) -> FocusedWindow {
+		FocusedWindow {
+			class: String::from("code"),
+			title: String::from(title),
+		}
+	}
+
+	#[test]
+	fn captures_fill_the_outputs() {
+		let rule = TitleRule {
+			class: String::from("^code$"),
+			title: String::from(r"^(?<file>\S+) - (\S+)"),
+			details: String::from("Editing ${file}"),
+			state: String::from("in $2 at {time}"),
+		};
+		let activity = rule
+			.apply(&window("main.rs - crate"), &Activity::default())
+			.unwrap()
+			.unwrap();
+
+		assert_eq!(activity.details.as_deref(), Some("Editing main.rs"));
+		assert_eq!(activity.state.as_deref(), Some("in crate at {time}"));
+	}
+
+	#[test]
+	fn captured_braces_are_escaped() {
+		let rule = TitleRule {
+			title: String::from("(.*)"),
+			details: String::from("$1"),
+			..Default::default()
+		};
+		let activity = rule
+			.apply(&window("{time} }{"), &Activity::default())
+			.unwrap()
+			.unwrap();
+
+		assert_eq!(activity.details.as_deref(), Some("{{time}} }}{{"));
+	}
+
+	#[test]
+	fn other_windows_dont_match() {
+		let rule = TitleRule {
+			class: String::from("^firefox$"),
+			..Default::default()
+		};
+
+		assert!(
+			rule.apply(&window("anything"), &Activity::default())
+				.unwrap()
+				.is_none()
+		);
+	}
+}
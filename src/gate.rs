@@ -0,0 +1,17 @@
+use std::sync::{
+	Arc,
+	atomic::{AtomicBool, Ordering},
+};
+
+/// Shared switch the app uses to pause a background watcher while nothing needs it
+#[derive(Debug, Clone, Default)]
+pub struct Gate(Arc<AtomicBool>);
+
+impl Gate {
+	pub fn set(&self, open: bool) {
+		self.0.store(open, Ordering::Relaxed);
+	}
+	pub fn is_open(&self) -> bool {
+		self.0.load(Ordering::Relaxed)
+	}
+}
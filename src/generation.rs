@@ -0,0 +1,15 @@
+/// Tags delayed messages like timers, so the ones scheduled before a restart can be told apart
+/// and dropped
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Generation(u64);
+
+impl Generation {
+	/// Invalidates everything handed out so far and returns the new generation
+	pub fn restart(&mut self) -> Generation {
+		self.0 = self.0.wrapping_add(1);
+		*self
+	}
+	pub fn is_current(&self, generation: Generation) -> bool {
+		*self == generation
+	}
+}
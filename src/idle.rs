@@ -0,0 +1,245 @@
+use std::{fmt, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+use crate::activity::{Activity, TimestampType};
+
+#[cfg(target_os = "linux")]
+pub use linux::IdleWatcher;
+
+pub const MIN_TIMEOUT_MINUTES: u64 = 1;
+pub const MAX_TIMEOUT_MINUTES: u64 = 120;
+pub static AWAY_ACTIONS: [AwayAction; 2] = [AwayAction::Activity, AwayAction::Clear];
+
+#[repr(u8)]
+#[derive(Default, Clone, Debug, Copy, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
+pub enum AwayAction {
+	#[default]
+	Activity = 0,
+	Clear = 1,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct Away {
+	pub enabled: bool,
+	pub timeout_minutes: u64,
+	pub action: AwayAction,
+	/// Falls back to a plain "Away" built from the edited activity when unset
+	pub activity: Option<Activity>,
+	#[serde(skip)]
+	pub active: bool,
+}
+
+/// How long the session has been without input
+#[derive(Debug, Clone)]
+pub struct IdleTime(pub Duration);
+
+impl fmt::Display for AwayAction {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			AwayAction::Activity => write!(f, "Show away activity"),
+			AwayAction::Clear => write!(f, "Clear presence"),
+		}
+	}
+}
+
+impl Default for Away {
+	fn default() -> Self {
+		Away {
+			enabled: false,
+			timeout_minutes: 5,
+			action: AwayAction::default(),
+			activity: None,
+			active: false,
+		}
+	}
+}
+
+impl Away {
+	pub fn is_idle(&self, idle: Duration) -> bool {
+		let minutes = self
+			.timeout_minutes
+			.clamp(MIN_TIMEOUT_MINUTES, MAX_TIMEOUT_MINUTES);
+
+		self.enabled && idle >= Duration::from_secs(minutes * 60)
+	}
+	pub fn activity(&self, fallback: &Activity) -> Activity {
+		let mut activity = self.activity.clone().unwrap_or_else(|| Activity {
+			details: Some(String::from("Away")),
+			timestamp_type: TimestampType::SinceLastUpdate,
+			large_key: fallback.large_key.clone(),
+			large_source: fallback.large_source,
+			large_text: fallback.large_text.clone(),
+			small_key: fallback.small_key.clone(),
+			small_source: fallback.small_source,
+			small_text: fallback.small_text.clone(),
+			..Default::default()
+		});
+		if activity.id.is_none() {
+			activity.id = fallback.id.clone();
+		}
+
+		activity
+	}
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+	use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+	use anyhow::anyhow;
+	use iced::futures::{SinkExt, channel::mpsc::UnboundedSender};
+	use log::{debug, error, warn};
+	use tokio::{task, time};
+	use zbus::{
+		Connection, Proxy,
+		proxy::{Builder, CacheProperties},
+	};
+
+	use super::IdleTime;
+	use crate::gate::Gate;
+
+	const POLL_INTERVAL: Duration = Duration::from_secs(5);
+	const BACKENDS: [Backend; 3] = [Backend::Mutter, Backend::ScreenSaver, Backend::Logind];
+
+	pub struct IdleWatcher {
+		send: UnboundedSender<IdleTime>,
+		gate: Gate,
+		session: Option<Connection>,
+		system: Option<Connection>,
+		backend: Option<Backend>,
+	}
+
+	#[derive(Clone, Copy, Debug)]
+	enum Backend {
+		/// GNOME
+		Mutter,
+		/// KDE and most other desktops
+		ScreenSaver,
+		/// Idle hint the desktop reports to systemd-logind
+		Logind,
+	}
+
+	impl IdleWatcher {
+		/// Polls the idle time while `gate` is open
+		pub fn spawn_thread(send: UnboundedSender<IdleTime>, gate: Gate) {
+			task::spawn(async move {
+				let mut state = IdleWatcher {
+					send,
+					gate,
+					session: Connection::session().await.ok(),
+					system: Connection::system().await.ok(),
+					backend: None,
+				};
+				if state.detect().await.is_none() {
+					warn!("No idle time source found, away detection wont work");
+					return;
+				}
+
+				loop {
+					if state.gate.is_open()
+						&& let Err(err) = state.event_loop().await
+					{
+						error!("{err}");
+					}
+					if state.send.is_closed() {
+						return;
+					}
+					time::sleep(POLL_INTERVAL).await;
+				}
+			});
+		}
+		async fn event_loop(&mut self) -> anyhow::Result<()> {
+			let idle = match self.backend {
+				Some(backend) => backend.idle_time(self).await,
+				None => self
+					.detect()
+					.await
+					.ok_or(anyhow!("No idle time source available")),
+			};
+			let idle = match idle {
+				Ok(idle) => idle,
+				Err(err) => {
+					// the desktop might have restarted, look for a source again next time
+					self.backend = None;
+					return Err(err);
+				}
+			};
+
+			self.send.send(IdleTime(idle)).await?;
+
+			Ok(())
+		}
+		async fn detect(&mut self) -> Option<Duration> {
+			for backend in BACKENDS {
+				match backend.idle_time(self).await {
+					Ok(idle) => {
+						debug!("Reading idle time from {backend:?}");
+						self.backend = Some(backend);
+						return Some(idle);
+					}
+					Err(err) => debug!("{backend:?} idle time unavailable: {err}"),
+				}
+			}
+
+			None
+		}
+	}
+
+	impl Backend {
+		async fn idle_time(&self, watcher: &IdleWatcher) -> anyhow::Result<Duration> {
+			let (conn, destination, path, interface) = match self {
+				Backend::Mutter => (
+					&watcher.session,
+					"org.gnome.Mutter.IdleMonitor",
+					"/org/gnome/Mutter/IdleMonitor/Core",
+					"org.gnome.Mutter.IdleMonitor",
+				),
+				Backend::ScreenSaver => (
+					&watcher.session,
+					"org.freedesktop.ScreenSaver",
+					"/org/freedesktop/ScreenSaver",
+					"org.freedesktop.ScreenSaver",
+				),
+				Backend::Logind => (
+					&watcher.system,
+					"org.freedesktop.login1",
+					"/org/freedesktop/login1/session/auto",
+					"org.freedesktop.login1.Session",
+				),
+			};
+			let Some(conn) = conn else {
+				return Err(anyhow!("Not connected to the bus"));
+			};
+			let proxy: Proxy = Builder::new(conn)
+				.destination(destination)?
+				.path(path)?
+				.interface(interface)?
+				.cache_properties(CacheProperties::No)
+				.build()
+				.await?;
+
+			Ok(match self {
+				Backend::Mutter => {
+					let millis: u64 = proxy.call("GetIdletime", &()).await?;
+					Duration::from_millis(millis)
+				}
+				Backend::ScreenSaver => {
+					let millis: u32 = proxy.call("GetSessionIdleTime", &()).await?;
+					Duration::from_millis(millis.into())
+				}
+				Backend::Logind => {
+					let idle: bool = proxy.get_property("IdleHint").await?;
+					if !idle {
+						return Ok(Duration::ZERO);
+					}
+					let since: u64 = proxy.get_property("IdleSinceHint").await?;
+					let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+					now.saturating_sub(Duration::from_micros(since))
+				}
+			})
+		}
+	}
+}
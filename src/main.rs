@@ -1,15 +1,36 @@
 mod activity;
+mod api;
 mod app;
+mod assets;
+mod autostart;
+mod calendar;
+mod cli;
+mod control;
+mod detection;
+mod focus;
+mod gate;
+mod generation;
+mod idle;
+mod mpris;
+mod notification;
+mod pomodoro;
 mod presence;
+mod preset;
+mod rotation;
+mod rule_list;
+mod schedule;
 mod settings;
+mod template;
 mod tray;
+mod validation;
+
+use std::process;
 
-use dark_light::Mode;
 use discord_rich_presence::activity::ActivityType;
-use iced::Theme;
 use iced_aw::ICED_AW_FONT_BYTES;
+use tracing_subscriber::{EnvFilter, filter::LevelFilter};
 
-use crate::app::App;
+use crate::{app::App, settings::SettingsFile};
 
 const TEXT_COLUMN_WIDTH: f32 = 100.;
 
@@ -21,13 +42,24 @@ static ACTIVITY_TYPES: [ActivityType; 4] = [
 ];
 
 fn main() -> iced::Result {
-	tracing_subscriber::fmt::init();
+	let log_level = SettingsFile::read()
+		.map(|v| v.settings.log_level)
+		.unwrap_or_default();
+	// RUST_LOG still wins over the setting
+	let filter = EnvFilter::builder()
+		.with_default_directive(LevelFilter::from(log_level).into())
+		.from_env_lossy();
+	tracing_subscriber::fmt().with_env_filter(filter).init();
+
+	let args: Vec<String> = std::env::args().skip(1).collect();
+	if !args.is_empty() {
+		process::exit(cli::run(&args));
+	}
+
 	iced::daemon(App::new, App::update, App::view)
 		.title("Discord presence")
-		.theme(|_, _| match dark_light::detect() {
-			Ok(Mode::Light) => Theme::Light,
-			_ => Theme::Dark,
-		})
+		.theme(App::theme)
+		.subscription(App::subscription)
 		.executor::<tokio::runtime::Runtime>()
 		.font(ICED_AW_FONT_BYTES)
 		.run()
@@ -0,0 +1,526 @@
+use std::time::Duration;
+
+use chrono::{TimeDelta, Utc};
+use discord_rich_presence::activity::ActivityType;
+
+use crate::{
+	activity::{Activity, TimestampType},
+	template,
+};
+
+#[cfg(target_os = "linux")]
+pub use linux::MprisWatcher;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track {
+	pub title: String,
+	pub artists: Vec<String>,
+	pub album: Option<String>,
+	pub length: Option<Duration>,
+	pub position: Duration,
+	pub playing: bool,
+}
+
+/// `None` once nothing is playing anymore
+#[derive(Debug, Clone)]
+pub struct NowPlaying(pub Option<Track>);
+
+impl Track {
+	/// Whether the change is worth another activity update, seeks further than `tolerance` count
+	pub fn differs(&self, other: &Track, elapsed: Duration, tolerance: Duration) -> bool {
+		if self.title != other.title
+			|| self.artists != other.artists
+			|| self.album != other.album
+			|| self.length != other.length
+			|| self.playing != other.playing
+		{
+			return true;
+		}
+		if !self.playing {
+			return false;
+		}
+
+		let expected = other.position + elapsed;
+		self.position.abs_diff(expected) > tolerance
+	}
+	/// Listening activity for the track, keeping the application id and images of `base`
+	pub fn activity(&self, base: &Activity) -> Activity {
+		// the texts still go through the template renderer
+		let artists = template::escape(&self.artists.join(", "));
+		let state = match (artists.is_empty(), self.playing) {
+			(true, true) => None,
+			(true, false) => Some(String::from("Paused")),
+			(false, true) => Some(format!("by {artists}")),
+			(false, false) => Some(format!("by {artists} (paused)")),
+		};
+		let started = TimeDelta::from_std(self.position)
+			.ok()
+			.and_then(|v| Utc::now().checked_sub_signed(v));
+		let ends = self
+			.length
+			.and_then(|v| TimeDelta::from_std(v).ok())
+			.zip(started)
+			.and_then(|(length, started)| started.checked_add_signed(length));
+
+		Activity {
+			id: base.id.clone(),
+			activity_type: ActivityType::Listening,
+			details: Some(template::escape(&self.title)).filter(|v| !v.is_empty()),
+			state,
+			timestamp_type: match (started, ends) {
+				(_, Some(_)) if self.playing => TimestampType::EndsAt,
+				(Some(_), _) if self.playing => TimestampType::Custom,
+				_ => TimestampType::SinceLastUpdate,
+			},
+			custom_timestamp: started,
+			start_timestamp: started,
+			end_timestamp: ends,
+			large_key: base.large_key.clone(),
+			large_source: base.large_source,
+			large_text: self.album.as_deref().map(template::escape),
+			small_key: base.small_key.clone(),
+			small_source: base.small_source,
+			..Default::default()
+		}
+	}
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+	use std::{
+		collections::HashMap,
+		time::{Duration, Instant},
+	};
+
+	use iced::futures::{SinkExt, channel::mpsc::UnboundedSender};
+	use log::{debug, error};
+	use tokio::{task, time};
+	use zbus::{
+		Connection, Proxy, fdo,
+		proxy::{Builder, CacheProperties},
+		zvariant::{OwnedValue, Value},
+	};
+
+	use super::{NowPlaying, Track};
+	use crate::gate::Gate;
+
+	const POLL_INTERVAL: Duration = Duration::from_secs(2);
+	const SEEK_TOLERANCE: Duration = Duration::from_secs(3);
+	const PLAYER_PREFIX: &str = "org.mpris.MediaPlayer2.";
+	const PLAYER_PATH: &str = "/org/mpris/MediaPlayer2";
+	const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+
+	pub struct MprisWatcher {
+		send: UnboundedSender<NowPlaying>,
+		gate: Gate,
+		conn: Connection,
+		last: Option<(Track, Instant)>,
+	}
+
+	impl MprisWatcher {
+		/// Polls the players while `gate` is open
+		pub fn spawn_thread(send: UnboundedSender<NowPlaying>, gate: Gate) {
+			task::spawn(async move {
+				let conn = match Connection::session().await {
+					Ok(conn) => conn,
+					Err(err) => {
+						error!("Failed to connect to the session bus: {err}");
+						return;
+					}
+				};
+				let mut state = MprisWatcher {
+					send,
+					gate,
+					conn,
+					last: None,
+				};
+
+				loop {
+					if !state.gate.is_open() {
+						state.last = None;
+					} else if let Err(err) = state.event_loop().await {
+						error!("{err}");
+					}
+					if state.send.is_closed() {
+						return;
+					}
+					time::sleep(POLL_INTERVAL).await;
+				}
+			});
+		}
+		async fn event_loop(&mut self) -> anyhow::Result<()> {
+			let track = current_track(&self.conn).await?;
+
+			let changed = match (&track, &self.last) {
+				(Some(track), Some((last, at))) => {
+					track.differs(last, at.elapsed(), SEEK_TOLERANCE)
+				}
+				(None, None) => false,
+				_ => true,
+			};
+			if !changed {
+				return Ok(());
+			}
+
+			self.last = track.clone().map(|v| (v, Instant::now()));
+			self.send.send(NowPlaying(track)).await?;
+
+			Ok(())
+		}
+	}
+
+	/// The first playing player on `conn`, falling back to a paused one
+	async fn current_track(conn: &Connection) -> zbus::Result<Option<Track>> {
+		let names = fdo::DBusProxy::new(conn).await?.list_names().await?;
+		let mut paused = None;
+
+		for name in names.iter().filter(|v| v.starts_with(PLAYER_PREFIX)) {
+			// players can vanish or misbehave, that shouldnt hide the other ones
+			let track = match read_track(conn, name.as_str()).await {
+				Ok(track) => track,
+				Err(err) => {
+					debug!("Failed to read {name}: {err}");
+					continue;
+				}
+			};
+			match track {
+				Some(track) if track.playing => return Ok(Some(track)),
+				Some(track) => {
+					paused.get_or_insert(track);
+				}
+				None => {}
+			}
+		}
+
+		Ok(paused)
+	}
+
+	async fn read_track(conn: &Connection, name: &str) -> zbus::Result<Option<Track>> {
+		let player: Proxy = Builder::new(conn)
+			.destination(name.to_string())?
+			.path(PLAYER_PATH)?
+			.interface(PLAYER_INTERFACE)?
+			.cache_properties(CacheProperties::No)
+			.build()
+			.await?;
+
+		let status: String = player.get_property("PlaybackStatus").await?;
+		let playing = match status.as_str() {
+			"Playing" => true,
+			"Paused" => false,
+			_ => return Ok(None),
+		};
+		let metadata: HashMap<String, OwnedValue> = player.get_property("Metadata").await?;
+		// not every player implements Position
+		let position: i64 = player.get_property("Position").await.unwrap_or_default();
+
+		let text = |key: &str| {
+			metadata
+				.get(key)
+				.and_then(|v| variant(v).downcast_ref::<&str>().ok())
+				.map(str::to_string)
+		};
+		let artists = match metadata.get("xesam:artist").map(|v| variant(v)) {
+			Some(Value::Array(artists)) => artists
+				.iter()
+				.filter_map(|v| variant(v).downcast_ref::<&str>().ok())
+				.map(str::to_string)
+				.collect(),
+			Some(Value::Str(artist)) => vec![artist.to_string()],
+			_ => Vec::new(),
+		};
+		let length = metadata.get("mpris:length").and_then(|v| {
+			let v = variant(v);
+			v.downcast_ref::<i64>()
+				.ok()
+				.or_else(|| v.downcast_ref::<u64>().ok().map(|v| v as i64))
+		});
+
+		Ok(Some(Track {
+			title: text("xesam:title").unwrap_or_default(),
+			artists,
+			album: text("xesam:album").filter(|v| !v.is_empty()),
+			length: length.map(micros),
+			position: micros(position),
+			playing,
+		}))
+	}
+
+	fn variant<'a>(value: &'a Value<'a>) -> &'a Value<'a> {
+		match value {
+			Value::Value(inner) => inner,
+			value => value,
+		}
+	}
+
+	fn micros(value: i64) -> Duration {
+		Duration::from_micros(value.max(0) as u64)
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use std::process::Stdio;
+
+		use tokio::{
+			io::{AsyncBufReadExt, BufReader},
+			process::{Child, Command},
+		};
+		use zbus::connection;
+
+		use super::*;
+
+		struct MockPlayer {
+			status: &'static str,
+			title: &'static str,
+		}
+
+		#[zbus::interface(name = "org.mpris.MediaPlayer2.Player")]
+		impl MockPlayer {
+			#[zbus(property)]
+			fn playback_status(&self) -> String {
+				self.status.to_string()
+			}
+			#[zbus(property)]
+			fn position(&self) -> i64 {
+				42_000_000
+			}
+			#[zbus(property)]
+			fn metadata(&self) -> HashMap<String, OwnedValue> {
+				let mut metadata = HashMap::new();
+				let mut insert = |key: &str, value: Value| {
+					metadata.insert(key.to_string(), value.try_into().unwrap());
+				};
+				insert("xesam:title", Value::from(self.title));
+				insert("xesam:album", Value::from("Album"));
+				insert("xesam:artist", Value::from(vec!["A", "B"]));
+				insert("mpris:length", Value::from(200_000_000i64));
+
+				metadata
+			}
+		}
+
+		/// A bus of its own, so the players of the desktop running the tests dont interfere.
+		/// `None` without a `dbus-daemon` to start
+		async fn private_bus() -> Option<(Child, String)> {
+			let mut daemon = Command::new("dbus-daemon")
+				.args(["--session", "--nofork", "--print-address"])
+				.stdout(Stdio::piped())
+				.stderr(Stdio::null())
+				.kill_on_drop(true)
+				.spawn()
+				.ok()?;
+			let mut address = String::new();
+			BufReader::new(daemon.stdout.take()?)
+				.read_line(&mut address)
+				.await
+				.ok()?;
+
+			Some((daemon, address.trim().to_string()))
+		}
+
+		async fn serve(address: &str, name: &str, player: MockPlayer) -> Connection {
+			connection::Builder::address(address)
+				.unwrap()
+				.name(format!("{PLAYER_PREFIX}{name}"))
+				.unwrap()
+				.serve_at(PLAYER_PATH, player)
+				.unwrap()
+				.build()
+				.await
+				.unwrap()
+		}
+
+		#[tokio::test]
+		async fn reads_the_playing_player() {
+			let Some((_daemon, address)) = private_bus().await else {
+				eprintln!("dbus-daemon is not available, skipping");
+				return;
+			};
+			let _paused = serve(
+				&address,
+				"paused",
+				MockPlayer {
+					status: "Paused",
+					title: "Paused song",
+				},
+			)
+			.await;
+			let _playing = serve(
+				&address,
+				"playing",
+				MockPlayer {
+					status: "Playing",
+					title: "Song",
+				},
+			)
+			.await;
+			let conn = connection::Builder::address(address.as_str())
+				.unwrap()
+				.build()
+				.await
+				.unwrap();
+
+			let track = current_track(&conn).await.unwrap();
+			assert_eq!(
+				track,
+				Some(Track {
+					title: String::from("Song"),
+					artists: vec![String::from("A"), String::from("B")],
+					album: Some(String::from("Album")),
+					length: Some(Duration::from_secs(200)),
+					position: Duration::from_secs(42),
+					playing: true,
+				})
+			);
+		}
+
+		#[tokio::test]
+		async fn falls_back_to_a_paused_player() {
+			let Some((_daemon, address)) = private_bus().await else {
+				eprintln!("dbus-daemon is not available, skipping");
+				return;
+			};
+			let _paused = serve(
+				&address,
+				"paused",
+				MockPlayer {
+					status: "Paused",
+					title: "Paused song",
+				},
+			)
+			.await;
+			let _stopped = serve(
+				&address,
+				"stopped",
+				MockPlayer {
+					status: "Stopped",
+					title: "Stopped song",
+				},
+			)
+			.await;
+			let conn = connection::Builder::address(address.as_str())
+				.unwrap()
+				.build()
+				.await
+				.unwrap();
+
+			let track = current_track(&conn).await.unwrap().unwrap();
+			assert_eq!(track.title, "Paused song");
+			assert!(!track.playing);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const TOLERANCE: Duration = Duration::from_secs(3);
+
+	fn track() -> Track {
+		Track {
+			title: String::from("Song"),
+			artists: vec![String::from("A"), String::from("B")],
+			album: Some(String::from("Album")),
+			length: Some(Duration::from_secs(200)),
+			position: Duration::from_secs(40),
+			playing: true,
+		}
+	}
+
+	#[test]
+	fn playback_moving_on_is_not_a_change() {
+		let later = Track {
+			position: Duration::from_secs(42),
+			..track()
+		};
+
+		assert!(!later.differs(&track(), Duration::from_secs(2), TOLERANCE));
+	}
+
+	#[test]
+	fn seeks_are_a_change() {
+		let seeked = Track {
+			position: Duration::from_secs(100),
+			..track()
+		};
+
+		assert!(seeked.differs(&track(), Duration::from_secs(2), TOLERANCE));
+	}
+
+	#[test]
+	fn paused_position_is_ignored() {
+		let paused = Track {
+			playing: false,
+			..track()
+		};
+		let seeked = Track {
+			position: Duration::from_secs(100),
+			..paused.clone()
+		};
+
+		assert!(!seeked.differs(&paused, Duration::from_secs(30), TOLERANCE));
+		assert!(paused.differs(&track(), Duration::ZERO, TOLERANCE));
+	}
+
+	#[test]
+	fn new_track_is_a_change() {
+		let next = Track {
+			title: String::from("Next"),
+			..track()
+		};
+
+		assert!(next.differs(&track(), Duration::ZERO, TOLERANCE));
+	}
+
+	#[test]
+	fn playing_activity() {
+		let base = Activity {
+			id: Some(String::from("1234")),
+			large_key: Some(String::from("cover")),
+			..Default::default()
+		};
+		let activity = track().activity(&base);
+
+		assert_eq!(activity.id.as_deref(), Some("1234"));
+		assert_eq!(activity.large_key.as_deref(), Some("cover"));
+		assert_eq!(activity.details.as_deref(), Some("Song"));
+		assert_eq!(activity.state.as_deref(), Some("by A, B"));
+		assert_eq!(activity.large_text.as_deref(), Some("Album"));
+		assert_eq!(activity.timestamp_type, TimestampType::EndsAt);
+
+		let (start, end) = (
+			activity.start_timestamp.unwrap(),
+			activity.end_timestamp.unwrap(),
+		);
+		assert_eq!(end - start, TimeDelta::seconds(200));
+		assert!((Utc::now() - start - TimeDelta::seconds(40)).abs() < TimeDelta::seconds(1));
+	}
+
+	#[test]
+	fn paused_activity() {
+		let track = Track {
+			playing: false,
+			..track()
+		};
+		let activity = track.activity(&Activity::default());
+
+		assert_eq!(activity.state.as_deref(), Some("by A, B (paused)"));
+		assert_eq!(activity.timestamp_type, TimestampType::SinceLastUpdate);
+	}
+
+	#[test]
+	fn braces_are_escaped() {
+		let track = Track {
+			title: String::from("{time}"),
+			artists: vec![String::from("}{")],
+			album: Some(String::from("{")),
+			..track()
+		};
+		let activity = track.activity(&Activity::default());
+
+		assert_eq!(activity.details.as_deref(), Some("{{time}}"));
+		assert_eq!(activity.state.as_deref(), Some("by }}{{"));
+		assert_eq!(activity.large_text.as_deref(), Some("{{"));
+	}
+}
@@ -0,0 +1,112 @@
+use std::{collections::VecDeque, time::Duration};
+
+use chrono::{DateTime, Local};
+
+#[cfg(target_os = "linux")]
+pub use linux::send_desktop;
+
+/// How long toasts of events stay up, errors stay until dismissed
+pub const TOAST_TIMEOUT: Duration = Duration::from_secs(8);
+const MAX_HISTORY: usize = 100;
+const MAX_TOASTS: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+	Info,
+	Error,
+}
+
+#[derive(Clone, Debug)]
+pub struct Notification {
+	pub id: u64,
+	pub level: Level,
+	pub text: String,
+	pub time: DateTime<Local>,
+	/// Still listed in the history, just not as a toast
+	pub dismissed: bool,
+}
+
+/// Errors and events of this session, newest first
+#[derive(Debug, Default)]
+pub struct Notifications {
+	pub history: VecDeque<Notification>,
+	next_id: u64,
+}
+
+impl Notifications {
+	pub fn push(&mut self, level: Level, text: String) -> u64 {
+		let id = self.next_id;
+		self.next_id = self.next_id.wrapping_add(1);
+		self.history.push_front(Notification {
+			id,
+			level,
+			text,
+			time: Local::now(),
+			dismissed: false,
+		});
+		self.history.truncate(MAX_HISTORY);
+
+		id
+	}
+	pub fn dismiss(&mut self, id: u64) {
+		if let Some(notification) = self.history.iter_mut().find(|v| v.id == id) {
+			notification.dismissed = true;
+		}
+	}
+	pub fn dismiss_all(&mut self) {
+		self.history.iter_mut().for_each(|v| v.dismissed = true);
+	}
+	pub fn toasts(&self) -> impl Iterator<Item = &Notification> {
+		self.history
+			.iter()
+			.filter(|v| !v.dismissed)
+			.take(MAX_TOASTS)
+	}
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+	use std::collections::HashMap;
+
+	use zbus::{
+		Connection, Proxy,
+		proxy::{Builder, CacheProperties},
+		zvariant::Value,
+	};
+
+	const APP_NAME: &str = "Discord presence";
+	/// Let the notification server decide
+	const DEFAULT_EXPIRE_TIMEOUT: i32 = -1;
+
+	/// Shows `body` through the freedesktop notification server of the session
+	pub async fn send_desktop(summary: &str, body: &str) -> anyhow::Result<()> {
+		let conn = Connection::session().await?;
+		let proxy: Proxy = Builder::new(&conn)
+			.destination("org.freedesktop.Notifications")?
+			.path("/org/freedesktop/Notifications")?
+			.interface("org.freedesktop.Notifications")?
+			.cache_properties(CacheProperties::No)
+			.build()
+			.await?;
+		let actions: Vec<&str> = Vec::new();
+		let hints: HashMap<&str, Value<'_>> = HashMap::new();
+
+		let _id: u32 = proxy
+			.call(
+				"Notify",
+				&(
+					APP_NAME,
+					0u32,
+					"",
+					summary,
+					body,
+					actions,
+					hints,
+					DEFAULT_EXPIRE_TIMEOUT,
+				),
+			)
+			.await?;
+
+		Ok(())
+	}
+}
@@ -0,0 +1,167 @@
+use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	activity::{Activity, TimestampType},
+	generation::Generation,
+};
+
+pub const MIN_PHASE_MINUTES: u64 = 1;
+pub const MAX_PHASE_MINUTES: u64 = 240;
+pub const MAX_CYCLES: u64 = 12;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+	Focus,
+	ShortBreak,
+	LongBreak,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct Pomodoro {
+	pub focus_minutes: u64,
+	pub break_minutes: u64,
+	pub long_break_minutes: u64,
+	/// Focus phases before a long break
+	pub cycles: u64,
+	/// Falls back to the edited activity when unset
+	pub focus_activity: Option<Activity>,
+	pub break_activity: Option<Activity>,
+	#[serde(skip)]
+	phase: Option<Phase>,
+	#[serde(skip)]
+	cycle: u64,
+	/// When the current phase began, so resending its activity doesnt restart the timer
+	#[serde(skip)]
+	phase_started: DateTime<Utc>,
+	#[serde(skip)]
+	generation: Generation,
+}
+
+pub struct PhasePlayback {
+	pub phase: Phase,
+	pub activity: Activity,
+	pub duration: Duration,
+	pub generation: Generation,
+}
+
+impl Phase {
+	pub fn label(&self) -> &'static str {
+		match self {
+			Phase::Focus => "Focus",
+			Phase::ShortBreak => "Short break",
+			Phase::LongBreak => "Long break",
+		}
+	}
+	fn details(&self) -> &'static str {
+		match self {
+			Phase::Focus => "Focusing",
+			Phase::ShortBreak => "On a break",
+			Phase::LongBreak => "On a long break",
+		}
+	}
+}
+
+impl Default for Pomodoro {
+	fn default() -> Self {
+		Pomodoro {
+			focus_minutes: 25,
+			break_minutes: 5,
+			long_break_minutes: 15,
+			cycles: 4,
+			focus_activity: None,
+			break_activity: None,
+			phase: None,
+			cycle: 0,
+			phase_started: DateTime::UNIX_EPOCH,
+			generation: Generation::default(),
+		}
+	}
+}
+
+impl Pomodoro {
+	pub fn phase(&self) -> Option<Phase> {
+		self.phase
+	}
+	pub fn is_running(&self) -> bool {
+		self.phase.is_some()
+	}
+	/// Human readable position in the session, e.g. `Focus 2/4`
+	pub fn status(&self) -> Option<String> {
+		let phase = self.phase?;
+		let cycle = self.cycle % self.cycles.max(1) + 1;
+
+		Some(match phase {
+			Phase::Focus => format!("{} {cycle}/{}", phase.label(), self.cycles),
+			_ => phase.label().to_string(),
+		})
+	}
+	pub fn start(&mut self, fallback: &Activity) -> Option<PhasePlayback> {
+		self.phase = Some(Phase::Focus);
+		self.cycle = 0;
+		self.phase_started = Utc::now();
+		self.generation.restart();
+
+		self.current(fallback)
+	}
+	pub fn stop(&mut self) {
+		self.phase = None;
+		self.generation.restart();
+	}
+	/// `None` if the timer was stopped or restarted since `generation` was handed out
+	pub fn advance(
+		&mut self,
+		generation: Generation,
+		fallback: &Activity,
+	) -> Option<PhasePlayback> {
+		let phase = self
+			.phase
+			.filter(|_| self.generation.is_current(generation))?;
+
+		self.phase = Some(match phase {
+			Phase::Focus => {
+				self.cycle += 1;
+				if self.cycle % self.cycles.max(1) == 0 {
+					Phase::LongBreak
+				} else {
+					Phase::ShortBreak
+				}
+			}
+			Phase::ShortBreak | Phase::LongBreak => Phase::Focus,
+		});
+		self.phase_started = Utc::now();
+
+		self.current(fallback)
+	}
+	fn current(&self, fallback: &Activity) -> Option<PhasePlayback> {
+		let phase = self.phase?;
+		let (minutes, activity) = match phase {
+			Phase::Focus => (self.focus_minutes, &self.focus_activity),
+			Phase::ShortBreak => (self.break_minutes, &self.break_activity),
+			Phase::LongBreak => (self.long_break_minutes, &self.break_activity),
+		};
+		let minutes = minutes.clamp(MIN_PHASE_MINUTES, MAX_PHASE_MINUTES);
+
+		let mut activity = activity.clone().unwrap_or_else(|| Activity {
+			details: Some(phase.details().to_string()),
+			state: self.status(),
+			..fallback.clone()
+		});
+		if activity.id.is_none() {
+			activity.id = fallback.id.clone();
+		}
+		activity.timestamp_type = TimestampType::EndsAt;
+		activity.start_timestamp = Some(self.phase_started);
+		activity.end_timestamp = Some(self.phase_started + TimeDelta::minutes(minutes as i64));
+
+		Some(PhasePlayback {
+			phase,
+			activity,
+			duration: Duration::from_secs(minutes * 60),
+			generation: self.generation,
+		})
+	}
+}
@@ -1,3 +1,5 @@
+use std::time::{Duration, Instant};
+
 use anyhow::anyhow;
 use chrono::{Local, Timelike, Utc};
 use discord_rich_presence::{
@@ -8,17 +10,30 @@ use iced::futures::{
 	SinkExt, StreamExt,
 	channel::mpsc::{UnboundedReceiver, UnboundedSender},
 };
-use log::error;
+use log::{error, info, warn};
+use serde_json::json;
 use thiserror::Error;
-use tokio::task;
+use tokio::{task, time};
 
-use crate::activity::TimestampType;
+use crate::activity::{Activity, TimestampType};
 use crate::app::message::MainThreadMessage;
+use crate::template::{self, TemplateContext};
+use crate::validation::{self, InvalidField};
+
+const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+const PING_OPCODE: u8 = 3;
+const TEMPLATE_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
 
 #[derive(Debug, Clone)]
 pub enum PresenceThreadMessage {
 	Err(String),
 	Connected,
+	/// Back after a dropped connection, with the activity from before already shown again
+	Reconnected,
+	ConnectionLost,
+	Reconnecting,
 	Disconnected,
 }
 pub struct Presence {
@@ -27,31 +42,69 @@ pub struct Presence {
 	pub client: Option<DiscordIpcClient>,
 	pub close: bool,
 	pub start_time: i64,
+	pub connected: bool,
+	pub last_activity: Option<Activity>,
+	pub reconnect_attempt: Option<u32>,
+	pub last_update: Instant,
+	pub template_refresh: bool,
+	/// Kept across messages so a busy channel cant keep pushing the health check back
+	pub next_check: time::Instant,
 }
 
 #[derive(Error, Debug)]
-enum PresenceError {
+pub enum PresenceError {
 	#[error("No id provided")]
 	NoIdError,
 	#[error("Not connected")]
 	NotConnected,
 	#[error("No date")]
 	NoDate,
+	#[error("Failed to connect to discord: {0}")]
+	ConnectionFailed(String),
+	#[error("Lost connection to discord: {0}")]
+	ConnectionLost(String),
+	#[error("Invalid activity: {0}")]
+	InvalidActivity(#[from] InvalidField),
+}
+
+impl PresenceError {
+	pub fn exit_code(&self) -> i32 {
+		match self {
+			PresenceError::NoIdError => 3,
+			PresenceError::NotConnected => 4,
+			PresenceError::NoDate => 5,
+			PresenceError::ConnectionFailed(_) => 6,
+			PresenceError::ConnectionLost(_) => 7,
+			PresenceError::InvalidActivity(_) => 8,
+		}
+	}
 }
 
 impl Presence {
+	pub fn new(
+		send: UnboundedSender<PresenceThreadMessage>,
+		recv: UnboundedReceiver<MainThreadMessage>,
+	) -> Self {
+		Presence {
+			send,
+			recv,
+			client: None,
+			close: false,
+			start_time: Utc::now().timestamp(),
+			connected: false,
+			last_activity: None,
+			reconnect_attempt: None,
+			last_update: Instant::now(),
+			template_refresh: false,
+			next_check: time::Instant::now() + HEALTH_CHECK_INTERVAL,
+		}
+	}
 	pub fn spawn_thread(
 		send: UnboundedSender<PresenceThreadMessage>,
 		recv: UnboundedReceiver<MainThreadMessage>,
 	) {
 		task::spawn(async {
-			let mut state = Presence {
-				send,
-				recv,
-				client: None,
-				close: false,
-				start_time: Utc::now().timestamp(),
-			};
+			let mut state = Presence::new(send, recv);
 
 			loop {
 				let Err(err) = state.event_loop().await else {
@@ -73,8 +126,22 @@ impl Presence {
 			}
 		});
 	}
-	async fn event_loop(&mut self) -> anyhow::Result<()> {
-		let Some(msg) = self.recv.next().await else {
+	pub async fn event_loop(&mut self) -> anyhow::Result<()> {
+		let msg = match (self.reconnect_attempt, self.connected) {
+			(Some(attempt), _) => tokio::select! {
+				msg = self.recv.next() => msg,
+				_ = time::sleep(reconnect_delay(attempt)) => return self.reconnect().await,
+			},
+			(None, true) => tokio::select! {
+				msg = self.recv.next() => msg,
+				_ = time::sleep_until(self.next_check) => {
+					self.next_check = time::Instant::now() + HEALTH_CHECK_INTERVAL;
+					return self.tick().await;
+				}
+			},
+			(None, false) => self.recv.next().await,
+		};
+		let Some(msg) = msg else {
 			return Ok(());
 		};
 
@@ -85,98 +152,60 @@ impl Presence {
 					.take_if(|v| v.client_id == id)
 					.unwrap_or(DiscordIpcClient::new(&id));
 
-				client.connect()?;
+				let res = client.connect();
 				let _ = self.client.insert(client);
+				res.map_err(|err| PresenceError::ConnectionFailed(err.to_string()))?;
+				self.connected = true;
+				self.reconnect_attempt = None;
 
 				self.send.send(PresenceThreadMessage::Connected).await?;
 			}
-			MainThreadMessage::Disconnect => {
-				let Some(client) = &mut self.client else {
-					//this shouldnt fail but just in case ig, better then a .expect cuz it wont panic
-					return Err(anyhow!(PresenceError::NotConnected));
-				};
-				client.close()?;
-				self.send.send(PresenceThreadMessage::Disconnected).await?;
-			}
-			MainThreadMessage::SetActivity(activity) => {
-				let Some(id) = activity.id else {
-					return Err(anyhow!(PresenceError::NoIdError));
-				};
-
+			MainThreadMessage::WaitForDiscord(id) => {
 				let mut client = self
 					.client
 					.take_if(|v| v.client_id == id)
 					.unwrap_or(DiscordIpcClient::new(&id));
 
-				let timestamp = match activity.timestamp_type {
-					TimestampType::SinceStart => {
-						let mut t = Timestamps::new();
-						t.start = Some(self.start_time);
-
-						t
-					}
-					TimestampType::LocalTime => {
-						let now = Local::now();
-						let offset_seconds: i64 = now.second() as i64
-							+ (now.minute() as i64 * 60)
-							+ (now.hour() as i64 * 3600);
-
-						let mut t = Timestamps::new();
-						t.start = Some(Utc::now().timestamp() - offset_seconds);
-
-						t
-					}
-					TimestampType::Custom => {
-						let Some(timestamp) = activity.custom_timestamp else {
-							return Err(anyhow!(PresenceError::NoDate));
-						};
-
-						let mut t = Timestamps::new();
-						t.start = Some(timestamp.timestamp());
-						t
-					}
-					TimestampType::SinceLastUpdate => {
-						let mut t = Timestamps::new();
-						t.start = Some(Utc::now().timestamp());
-						t
-					}
-				};
-				let mut buttons = Vec::new();
-				if let (Some(text), Some(url)) = (
-					activity.button1_text.as_ref(),
-					activity.button1_url.as_ref(),
-				) {
-					buttons.push(Button::new(text, url));
+				let res = client.connect();
+				let _ = self.client.insert(client);
+				self.connected = true;
+				if let Err(err) = res {
+					// discord isnt up yet, keep retrying with the reconnect backoff
+					info!("Waiting for discord: {err}");
+					return self.start_reconnect().await;
 				}
-				if let (Some(text), Some(url)) = (
-					activity.button2_text.as_ref(),
-					activity.button2_url.as_ref(),
-				) {
-					buttons.push(Button::new(text, url));
+				self.reconnect_attempt = None;
+
+				self.send.send(PresenceThreadMessage::Connected).await?;
+			}
+			MainThreadMessage::Disconnect => {
+				self.connected = false;
+				if self.reconnect_attempt.take().is_none() {
+					let Some(client) = &mut self.client else {
+						//this shouldnt fail but just in case ig, better then a .expect cuz it wont panic
+						return Err(anyhow!(PresenceError::NotConnected));
+					};
+					client.close()?;
+				}
+				self.send.send(PresenceThreadMessage::Disconnected).await?;
+			}
+			MainThreadMessage::SetActivity(activity) => {
+				if self.reconnect_attempt.is_some() {
+					// applied once the connection is back
+					self.last_activity = Some(activity);
+					return Ok(());
 				}
-				let discord_activity = drp::activity::Activity {
-					state: activity.state.as_deref(),
-					details: activity.details.as_deref(),
-					timestamps: Some(timestamp),
-					activity_type: Some(activity.activity_type),
-					party: match (activity.party_max, activity.party_size) {
-						(Some(max), Some(size)) => Some(Party {
-							id: None,
-							size: Some([size, max]),
-						}),
-						_ => None,
-					},
-					buttons: Some(buttons),
-					assets: Some(Assets {
-						large_image: activity.large_key.as_deref(),
-						large_text: activity.large_text.as_deref(),
-						small_image: activity.small_key.as_deref(),
-						small_text: activity.small_text.as_deref(),
-					}),
-					..Default::default()
+				self.apply_activity(activity).await?;
+			}
+			MainThreadMessage::ClearActivity => {
+				let Some(client) = &mut self.client else {
+					return Err(anyhow!(PresenceError::NotConnected));
 				};
-				client.set_activity(discord_activity)?;
-				self.client = Some(client);
+				client.clear_activity()?;
+				self.last_activity = None;
+			}
+			MainThreadMessage::TemplateRefresh(enabled) => {
+				self.template_refresh = enabled;
 			}
 			MainThreadMessage::Exit => {
 				self.close = true;
@@ -184,4 +213,212 @@ impl Presence {
 		}
 		Ok(())
 	}
+	async fn apply_activity(&mut self, activity: Activity) -> anyhow::Result<()> {
+		let res = self.set_activity(&activity);
+		self.last_activity = Some(activity);
+
+		match res {
+			Err(err)
+				if matches!(
+					err.downcast_ref::<PresenceError>(),
+					Some(PresenceError::ConnectionLost(_))
+				) =>
+			{
+				warn!("{err}");
+				self.start_reconnect().await
+			}
+			res => res,
+		}
+	}
+	async fn tick(&mut self) -> anyhow::Result<()> {
+		self.check_connection().await?;
+		if self.reconnect_attempt.is_some()
+			|| self.last_update.elapsed() < TEMPLATE_REFRESH_INTERVAL
+		{
+			return Ok(());
+		}
+
+		let Some(activity) = self
+			.last_activity
+			.as_ref()
+			.filter(|v| self.template_refresh && template::has_variables(v))
+		else {
+			return Ok(());
+		};
+		self.apply_activity(activity.clone()).await
+	}
+	async fn check_connection(&mut self) -> anyhow::Result<()> {
+		// only written, waiting for the PONG could block on a discord that stopped answering
+		// with no way to cut the read short. A pipe discord closed already fails the write
+		let alive = self
+			.client
+			.as_mut()
+			.is_some_and(|client| client.send(json!({}), PING_OPCODE).is_ok());
+		if alive {
+			return Ok(());
+		}
+
+		warn!("Discord IPC connection is dead");
+		self.send
+			.send(PresenceThreadMessage::ConnectionLost)
+			.await?;
+		self.start_reconnect().await
+	}
+	async fn start_reconnect(&mut self) -> anyhow::Result<()> {
+		self.reconnect_attempt = Some(0);
+		self.send.send(PresenceThreadMessage::Reconnecting).await?;
+
+		Ok(())
+	}
+	async fn reconnect(&mut self) -> anyhow::Result<()> {
+		let Some(client) = &mut self.client else {
+			self.reconnect_attempt = None;
+			return Err(anyhow!(PresenceError::NotConnected));
+		};
+
+		if let Err(err) = client.connect() {
+			warn!("Reconnect failed: {err}");
+			self.reconnect_attempt = self.reconnect_attempt.map(|v| v.saturating_add(1));
+			return Ok(());
+		}
+		self.reconnect_attempt = None;
+
+		let Some(activity) = self.last_activity.take() else {
+			self.send.send(PresenceThreadMessage::Connected).await?;
+			return Ok(());
+		};
+		self.send.send(PresenceThreadMessage::Reconnected).await?;
+		self.apply_activity(activity).await
+	}
+	fn set_activity(&mut self, activity: &Activity) -> anyhow::Result<()> {
+		let activity = &template::render_activity(activity, &TemplateContext::current())?;
+		// discord drops the whole activity over a single bad field
+		validation::validate(activity)
+			.into_result()
+			.map_err(|err| anyhow!(PresenceError::from(err)))?;
+		let Some(id) = &activity.id else {
+			return Err(anyhow!(PresenceError::NoIdError));
+		};
+
+		let timestamp = match activity.timestamp_type {
+			TimestampType::SinceStart => {
+				let mut t = Timestamps::new();
+				t.start = Some(self.start_time);
+
+				t
+			}
+			TimestampType::LocalTime => {
+				let now = Local::now();
+				let offset_seconds: i64 =
+					now.second() as i64 + (now.minute() as i64 * 60) + (now.hour() as i64 * 3600);
+
+				let mut t = Timestamps::new();
+				t.start = Some(Utc::now().timestamp() - offset_seconds);
+
+				t
+			}
+			TimestampType::Custom => {
+				let Some(timestamp) = activity.custom_timestamp else {
+					return Err(anyhow!(PresenceError::NoDate));
+				};
+
+				let mut t = Timestamps::new();
+				t.start = Some(timestamp.timestamp());
+				t
+			}
+			TimestampType::SinceLastUpdate => {
+				let mut t = Timestamps::new();
+				t.start = Some(Utc::now().timestamp());
+				t
+			}
+			TimestampType::EndsAt => {
+				let Some(end) = activity.end_timestamp else {
+					return Err(anyhow!(PresenceError::NoDate));
+				};
+
+				let mut t = Timestamps::new();
+				t.start = activity.start_timestamp.map(|v| v.timestamp());
+				t.end = Some(end.timestamp());
+				t
+			}
+			TimestampType::Countdown => {
+				let Some(countdown) = activity.countdown else {
+					return Err(anyhow!(PresenceError::NoDate));
+				};
+				let now = Utc::now().timestamp();
+
+				let mut t = Timestamps::new();
+				t.start = Some(now);
+				t.end = Some(now.saturating_add_unsigned(countdown));
+				t
+			}
+		};
+		let mut buttons = Vec::new();
+		if let (Some(text), Some(url)) = (
+			activity.button1_text.as_ref(),
+			activity.button1_url.as_ref(),
+		) {
+			buttons.push(Button::new(text, url));
+		}
+		if let (Some(text), Some(url)) = (
+			activity.button2_text.as_ref(),
+			activity.button2_url.as_ref(),
+		) {
+			buttons.push(Button::new(text, url));
+		}
+		let discord_activity = drp::activity::Activity {
+			state: activity.state.as_deref(),
+			details: activity.details.as_deref(),
+			timestamps: Some(timestamp),
+			activity_type: Some(activity.activity_type.clone()),
+			party: match (activity.party_max, activity.party_size) {
+				(Some(max), Some(size)) => Some(Party {
+					id: None,
+					size: Some([size, max]),
+				}),
+				_ => None,
+			},
+			buttons: Some(buttons),
+			assets: Some(Assets {
+				large_image: activity.large_key.as_deref(),
+				large_text: activity.large_text.as_deref(),
+				small_image: activity.small_key.as_deref(),
+				small_text: activity.small_text.as_deref(),
+			}),
+			..Default::default()
+		};
+
+		let mut client = self
+			.client
+			.take_if(|v| v.client_id == *id)
+			.unwrap_or(DiscordIpcClient::new(id));
+		let res = client.set_activity(discord_activity);
+		self.client = Some(client);
+
+		let Err(err) = res else {
+			self.last_update = Instant::now();
+			return Ok(());
+		};
+		if self.connected && is_disconnect(&err) {
+			return Err(anyhow!(PresenceError::ConnectionLost(err.to_string())));
+		}
+		Err(anyhow!(err))
+	}
+}
+
+/// Whether `err` means the socket to discord is gone, rather than discord refusing the payload
+fn is_disconnect(err: &drp::error::Error) -> bool {
+	matches!(
+		err,
+		drp::error::Error::NotConnected
+			| drp::error::Error::ReadError(_)
+			| drp::error::Error::WriteError(_)
+			| drp::error::Error::FlushError(_)
+	)
+}
+
+fn reconnect_delay(attempt: u32) -> Duration {
+	RECONNECT_BASE_DELAY
+		.saturating_mul(2u32.saturating_pow(attempt))
+		.min(RECONNECT_MAX_DELAY)
 }
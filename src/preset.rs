@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+
+use crate::activity::Activity;
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct Preset {
+	pub name: String,
+	pub activity: Activity,
+}
+
+impl Preset {
+	pub fn new(name: String, activity: Activity) -> Self {
+		Preset { name, activity }
+	}
+}
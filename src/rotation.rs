@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{activity::Activity, generation::Generation};
+
+// discord only accepts 5 activity updates per 20 seconds
+pub const MIN_FRAME_DURATION: u64 = 5;
+pub const MAX_FRAME_DURATION: u64 = 24 * 3600;
+const DEFAULT_FRAME_DURATION: u64 = 30;
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Frame {
+	pub activity: Activity,
+	pub duration: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct Rotation {
+	#[serde(default)]
+	pub frames: Vec<Frame>,
+	#[serde(skip)]
+	running: bool,
+	#[serde(skip)]
+	index: usize,
+	#[serde(skip)]
+	generation: Generation,
+}
+
+pub struct FramePlayback {
+	pub activity: Activity,
+	pub duration: Duration,
+	pub generation: Generation,
+}
+
+impl Frame {
+	pub fn new(activity: Activity) -> Self {
+		Frame {
+			activity,
+			duration: DEFAULT_FRAME_DURATION,
+		}
+	}
+}
+
+impl Rotation {
+	pub fn is_running(&self) -> bool {
+		self.running
+	}
+	pub fn start(&mut self) -> Option<FramePlayback> {
+		if self.frames.is_empty() {
+			return None;
+		}
+		self.running = true;
+		self.index = 0;
+		self.generation.restart();
+
+		self.current()
+	}
+	pub fn stop(&mut self) {
+		self.running = false;
+		self.generation.restart();
+	}
+	/// `None` if the rotation was stopped or restarted since `generation` was handed out
+	pub fn advance(&mut self, generation: Generation) -> Option<FramePlayback> {
+		if !self.running || !self.generation.is_current(generation) {
+			return None;
+		}
+		if self.frames.is_empty() {
+			self.stop();
+			return None;
+		}
+		self.index = (self.index + 1) % self.frames.len();
+
+		self.current()
+	}
+	pub fn remove(&mut self, index: usize) {
+		if index >= self.frames.len() {
+			return;
+		}
+		self.frames.remove(index);
+		if self.frames.is_empty() {
+			self.stop();
+		} else if self.index >= self.frames.len() {
+			self.index = 0;
+		}
+	}
+	fn current(&self) -> Option<FramePlayback> {
+		let frame = self.frames.get(self.index)?;
+
+		Some(FramePlayback {
+			activity: frame.activity.clone(),
+			duration: Duration::from_secs(
+				frame.duration.clamp(MIN_FRAME_DURATION, MAX_FRAME_DURATION),
+			),
+			generation: self.generation,
+		})
+	}
+}
@@ -0,0 +1,13 @@
+/// Out of range indices are ignored, the buttons could belong to an outdated view
+pub fn remove<T>(list: &mut Vec<T>, index: usize) {
+	if index < list.len() {
+		list.remove(index);
+	}
+}
+
+/// Swaps the entry at `index` with the one above it
+pub fn move_up<T>(list: &mut [T], index: usize) {
+	if index > 0 && index < list.len() {
+		list.swap(index - 1, index);
+	}
+}
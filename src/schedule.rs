@@ -0,0 +1,132 @@
+use std::{fmt, time::Duration};
+
+use chrono::{Datelike, Days, Local, NaiveDateTime, NaiveTime, Timelike, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::{activity::Activity, generation::Generation};
+
+pub const WEEKDAYS: [Weekday; 7] = [
+	Weekday::Mon,
+	Weekday::Tue,
+	Weekday::Wed,
+	Weekday::Thu,
+	Weekday::Fri,
+	Weekday::Sat,
+	Weekday::Sun,
+];
+const TIME_STEP: u32 = 15;
+// re-check at least this often so clock and timezone changes get picked up
+const MAX_WAIT: Duration = Duration::from_secs(3600);
+
+/// Minutes after local midnight
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TimeOfDay(pub u32);
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ScheduleRule {
+	/// Bit per weekday, monday is the lowest
+	pub days: u8,
+	pub start: TimeOfDay,
+	/// An end before the start runs past midnight
+	pub end: TimeOfDay,
+	pub activity: Activity,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct Schedule {
+	#[serde(default)]
+	pub enabled: bool,
+	#[serde(default)]
+	pub rules: Vec<ScheduleRule>,
+	#[serde(skip)]
+	pub active: Option<usize>,
+	#[serde(skip)]
+	pub generation: Generation,
+}
+
+impl TimeOfDay {
+	/// Every selectable time, in 15 minute steps
+	pub fn all() -> Vec<TimeOfDay> {
+		(0..24 * 60)
+			.step_by(TIME_STEP as usize)
+			.map(TimeOfDay)
+			.collect()
+	}
+	fn naive(&self) -> NaiveTime {
+		NaiveTime::from_hms_opt(self.0 / 60 % 24, self.0 % 60, 0).unwrap_or_default()
+	}
+}
+
+impl fmt::Display for TimeOfDay {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{:02}:{:02}", self.0 / 60 % 24, self.0 % 60)
+	}
+}
+
+impl ScheduleRule {
+	pub fn new(activity: Activity) -> Self {
+		ScheduleRule {
+			// weekdays
+			days: 0b0011111,
+			start: TimeOfDay(9 * 60),
+			end: TimeOfDay(17 * 60),
+			activity,
+		}
+	}
+	pub fn has_day(&self, day: Weekday) -> bool {
+		self.days & (1 << day.num_days_from_monday()) != 0
+	}
+	pub fn toggle_day(&mut self, day: Weekday) {
+		self.days ^= 1 << day.num_days_from_monday();
+	}
+	pub fn contains(&self, at: NaiveDateTime) -> bool {
+		let minute = TimeOfDay(at.hour() * 60 + at.minute());
+		let day = at.weekday();
+
+		if self.start < self.end {
+			self.has_day(day) && self.start <= minute && minute < self.end
+		} else {
+			// the part after midnight belongs to the day the rule started on
+			(self.has_day(day) && minute >= self.start)
+				|| (self.has_day(day.pred()) && minute < self.end)
+		}
+	}
+}
+
+impl Schedule {
+	/// First rule containing `at`, rules higher in the list win
+	pub fn active_at(&self, at: NaiveDateTime) -> Option<usize> {
+		self.rules.iter().position(|rule| rule.contains(at))
+	}
+	/// When the active rule changes next and which rule takes over, looking a week ahead
+	pub fn next_change(&self, now: NaiveDateTime) -> Option<(NaiveDateTime, Option<usize>)> {
+		let current = self.active_at(now);
+		let mut boundaries: Vec<NaiveDateTime> = (0..=7)
+			.filter_map(|offset| now.date().checked_add_days(Days::new(offset)))
+			.flat_map(|date| {
+				self.rules
+					.iter()
+					.flat_map(move |rule| [rule.start, rule.end])
+					.map(move |time| date.and_time(time.naive()))
+			})
+			.filter(|at| *at > now)
+			.collect();
+		boundaries.sort();
+
+		boundaries
+			.into_iter()
+			.map(|at| (at, self.active_at(at)))
+			.find(|(_, active)| *active != current)
+	}
+	pub fn is_current(&self, generation: Generation) -> bool {
+		self.enabled && self.generation.is_current(generation)
+	}
+	/// How long to sleep before checking the schedule again
+	pub fn wait(&self) -> Duration {
+		let now = Local::now().naive_local();
+
+		self.next_change(now)
+			.and_then(|(at, _)| (at - now).to_std().ok())
+			.map_or(MAX_WAIT, |v| v.min(MAX_WAIT))
+	}
+}
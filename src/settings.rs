@@ -1,22 +1,168 @@
-use std::fs;
+use std::{fmt, fs, path::PathBuf};
 
 use anyhow::{Context, anyhow};
 use image::EncodableLayout;
 use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use tracing_subscriber::filter::LevelFilter;
 
-use crate::{activity::Activity, app::App};
+use crate::{
+	activity::{Activity, TimestampType},
+	app::App,
+	assets,
+	calendar::Calendar,
+	detection::ProcessRules,
+	focus::TitleRules,
+	idle::Away,
+	pomodoro::Pomodoro,
+	preset::Preset,
+	rotation::Rotation,
+	schedule::Schedule,
+};
+
+pub const DEFAULT_API_PORT: u16 = 7463;
+
+pub static THEMES: [ThemeSetting; 3] = [
+	ThemeSetting::System,
+	ThemeSetting::Light,
+	ThemeSetting::Dark,
+];
+pub static LOG_LEVELS: [LogLevel; 5] = [
+	LogLevel::Error,
+	LogLevel::Warn,
+	LogLevel::Info,
+	LogLevel::Debug,
+	LogLevel::Trace,
+];
+
+#[repr(u8)]
+#[derive(Default, Clone, Debug, Copy, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
+pub enum ThemeSetting {
+	#[default]
+	System = 0,
+	Light = 1,
+	Dark = 2,
+}
+
+#[repr(u8)]
+#[derive(Default, Clone, Debug, Copy, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
+pub enum LogLevel {
+	Error = 1,
+	Warn = 2,
+	#[default]
+	Info = 3,
+	Debug = 4,
+	Trace = 5,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct WindowGeometry {
+	pub width: f32,
+	pub height: f32,
+	pub x: Option<f32>,
+	pub y: Option<f32>,
+}
 
 #[derive(Serialize, Deserialize, Default, Clone)]
-pub struct Settings {}
+#[serde(default)]
+pub struct Settings {
+	pub auto_connect: bool,
+	pub start_minimized: bool,
+	pub remember_window: bool,
+	pub window: Option<WindowGeometry>,
+	pub default_timestamp: TimestampType,
+	pub theme: ThemeSetting,
+	pub log_level: LogLevel,
+	pub api_port: Option<u16>,
+	pub autostart: bool,
+	pub mpris: bool,
+	/// Mirrors notifications to the desktop while the window is closed
+	pub desktop_notifications: bool,
+	/// Fills in the placeholders of the shown activity again every minute
+	pub template_refresh: bool,
+}
+
+impl fmt::Display for ThemeSetting {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			ThemeSetting::System => write!(f, "System"),
+			ThemeSetting::Light => write!(f, "Light"),
+			ThemeSetting::Dark => write!(f, "Dark"),
+		}
+	}
+}
+
+impl fmt::Display for LogLevel {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			LogLevel::Error => write!(f, "Error"),
+			LogLevel::Warn => write!(f, "Warn"),
+			LogLevel::Info => write!(f, "Info"),
+			LogLevel::Debug => write!(f, "Debug"),
+			LogLevel::Trace => write!(f, "Trace"),
+		}
+	}
+}
+
+impl From<LogLevel> for LevelFilter {
+	fn from(value: LogLevel) -> Self {
+		match value {
+			LogLevel::Error => LevelFilter::ERROR,
+			LogLevel::Warn => LevelFilter::WARN,
+			LogLevel::Info => LevelFilter::INFO,
+			LogLevel::Debug => LevelFilter::DEBUG,
+			LogLevel::Trace => LevelFilter::TRACE,
+		}
+	}
+}
 
 #[derive(Serialize, Deserialize, Default)]
 pub struct SettingsFile {
 	pub settings: Settings,
 	pub activity: Activity,
+	#[serde(default)]
+	pub presets: Vec<Preset>,
+	#[serde(default)]
+	pub rotation: Rotation,
+	#[serde(default)]
+	pub process_rules: ProcessRules,
+	#[serde(default)]
+	pub title_rules: TitleRules,
+	#[serde(default)]
+	pub pomodoro: Pomodoro,
+	#[serde(default)]
+	pub schedule: Schedule,
+	#[serde(default)]
+	pub calendar: Calendar,
+	#[serde(default)]
+	pub away: Away,
 }
 
 static APP_ID: &str = "me.tofixrs.discord-presence";
 
+pub fn presets_dir() -> Option<PathBuf> {
+	path::storage_dir(APP_ID).map(|p| p.join("presets"))
+}
+
+/// Cached asset lists and thumbnails of one application
+pub fn assets_dir(app_id: &str) -> Option<PathBuf> {
+	if !assets::is_snowflake(app_id) {
+		return None;
+	}
+
+	path::storage_dir(APP_ID).map(|p| p.join("assets").join(app_id))
+}
+
+/// Downloaded previews of image URLs
+pub fn image_cache_dir() -> Option<PathBuf> {
+	path::storage_dir(APP_ID).map(|p| p.join("image-cache"))
+}
+
+/// XDG autostart entry, only available on linux and the BSDs
+pub fn autostart_file() -> Option<PathBuf> {
+	path::config_dir().map(|p| p.join("autostart").join(format!("{APP_ID}.desktop")))
+}
+
 impl SettingsFile {
 	fn get_location() -> std::path::PathBuf {
 		path::storage_dir(APP_ID)
@@ -49,6 +195,14 @@ impl From<&App> for SettingsFile {
 		SettingsFile {
 			settings: value.settings.clone(),
 			activity: value.activity.clone(),
+			presets: value.presets.clone(),
+			rotation: value.rotation.clone(),
+			process_rules: value.process_rules.clone(),
+			title_rules: value.title_rules.clone(),
+			pomodoro: value.pomodoro.clone(),
+			schedule: value.schedule.clone(),
+			calendar: value.calendar.clone(),
+			away: value.away.clone(),
 		}
 	}
 }
@@ -112,24 +266,31 @@ mod path {
 		}
 	}
 
+	/// `$XDG_CONFIG_HOME`, falling back to `~/.config`. Only defined on unix-likes
+	pub fn config_dir() -> Option<PathBuf> {
+		use std::env::var_os;
+		match OperatingSystem::from_target_os() {
+			OperatingSystem::Nix => var_os("XDG_CONFIG_HOME")
+				.map(PathBuf::from)
+				.filter(|p| p.is_absolute())
+				.or_else(|| home_dir().map(|p| p.join(".config"))),
+			_ => None,
+		}
+	}
+
 	/// On native, the path is:
 	/// * Linux:   `/home/UserName/.config/APP_ID`
 	/// * macOS:   `/Users/UserName/Library/Preferences/APP_ID`
 	/// * Windows: `C:\Users\UserName\AppData\Roaming\APP_ID\data`
 	pub fn storage_dir(app_id: &str) -> Option<PathBuf> {
-		use std::env::var_os;
 		match OperatingSystem::from_target_os() {
-			OperatingSystem::Nix => var_os("XDG_CONFIG_HOME")
-				.map(PathBuf::from)
-				.filter(|p| p.is_absolute())
-				.or_else(|| home_dir().map(|p| p.join(".config")))
-				.map(|p| {
-					p.join(
-						app_id
-							.to_lowercase()
-							.replace(|c: char| c.is_ascii_whitespace(), ""),
-					)
-				}),
+			OperatingSystem::Nix => config_dir().map(|p| {
+				p.join(
+					app_id
+						.to_lowercase()
+						.replace(|c: char| c.is_ascii_whitespace(), ""),
+				)
+			}),
 			OperatingSystem::Mac => home_dir().map(|p| {
 				p.join("Library")
 					.join("Preferences")
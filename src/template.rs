@@ -0,0 +1,300 @@
+use std::{env, fs, time::Duration};
+
+use chrono::{DateTime, Local};
+use thiserror::Error;
+
+use crate::activity::Activity;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum TemplateError {
+	#[error("Unknown template variable {{{0}}}")]
+	UnknownVariable(String),
+	#[error("Unclosed template variable starting at {0}")]
+	Unclosed(usize),
+	#[error("Unmatched }} at {0}")]
+	UnmatchedClose(usize),
+	#[error("Environment variable {0} is not set")]
+	MissingEnv(String),
+	#[error("{0} is not available on this system")]
+	Unavailable(&'static str),
+}
+
+pub struct TemplateContext {
+	pub now: DateTime<Local>,
+	pub hostname: Option<String>,
+	pub uptime: Option<Duration>,
+}
+
+impl TemplateContext {
+	pub fn current() -> Self {
+		TemplateContext {
+			now: Local::now(),
+			hostname: hostname(),
+			uptime: uptime(),
+		}
+	}
+	fn lookup(&self, name: &str) -> Result<String, TemplateError> {
+		if let Some(var) = name.strip_prefix("env:") {
+			return env::var(var).map_err(|_| TemplateError::MissingEnv(var.to_string()));
+		}
+
+		match name {
+			"time" => Ok(self.now.format("%H:%M").to_string()),
+			"date" => Ok(self.now.format("%Y-%m-%d").to_string()),
+			"hostname" => self
+				.hostname
+				.clone()
+				.ok_or(TemplateError::Unavailable("hostname")),
+			"uptime" => self
+				.uptime
+				.map(format_duration)
+				.ok_or(TemplateError::Unavailable("uptime")),
+			_ => Err(TemplateError::UnknownVariable(name.to_string())),
+		}
+	}
+}
+
+/// Expands `{variable}` placeholders, `{{` and `}}` are literal braces
+pub fn render(template: &str, ctx: &TemplateContext) -> Result<String, TemplateError> {
+	let mut out = String::with_capacity(template.len());
+	let mut rest = template;
+
+	while let Some(i) = rest.find(['{', '}']) {
+		let offset = template.len() - rest.len() + i;
+		out.push_str(&rest[..i]);
+		let brace = &rest[i..i + 1];
+		rest = &rest[i + 1..];
+
+		if let Some(after) = rest.strip_prefix(brace) {
+			out.push_str(brace);
+			rest = after;
+			continue;
+		}
+		if brace == "}" {
+			return Err(TemplateError::UnmatchedClose(offset));
+		}
+
+		let Some(end) = rest.find('}') else {
+			return Err(TemplateError::Unclosed(offset));
+		};
+		out.push_str(&ctx.lookup(rest[..end].trim())?);
+		rest = &rest[end + 1..];
+	}
+	out.push_str(rest);
+
+	Ok(out)
+}
+
+/// Doubles the braces of `text`, so outside text like window titles renders as is
+pub fn escape(text: &str) -> String {
+	text.replace('{', "{{").replace('}', "}}")
+}
+
+pub fn has_variables(activity: &Activity) -> bool {
+	text_fields(activity)
+		.iter()
+		.any(|field| field.as_ref().is_some_and(|v| v.contains('{')))
+}
+
+pub fn render_activity(
+	activity: &Activity,
+	ctx: &TemplateContext,
+) -> Result<Activity, TemplateError> {
+	let render_field =
+		|field: &Option<String>| field.as_deref().map(|v| render(v, ctx)).transpose();
+
+	Ok(Activity {
+		details: render_field(&activity.details)?,
+		state: render_field(&activity.state)?,
+		large_text: render_field(&activity.large_text)?,
+		small_text: render_field(&activity.small_text)?,
+		button1_text: render_field(&activity.button1_text)?,
+		button2_text: render_field(&activity.button2_text)?,
+		..activity.clone()
+	})
+}
+
+fn text_fields(activity: &Activity) -> [&Option<String>; 6] {
+	[
+		&activity.details,
+		&activity.state,
+		&activity.large_text,
+		&activity.small_text,
+		&activity.button1_text,
+		&activity.button2_text,
+	]
+}
+
+fn format_duration(duration: Duration) -> String {
+	let minutes = duration.as_secs() / 60;
+	let (days, hours, minutes) = (minutes / 1440, minutes / 60 % 24, minutes % 60);
+
+	if days > 0 {
+		format!("{days}d {hours}h {minutes}m")
+	} else if hours > 0 {
+		format!("{hours}h {minutes}m")
+	} else {
+		format!("{minutes}m")
+	}
+}
+
+fn hostname() -> Option<String> {
+	fs::read_to_string("/proc/sys/kernel/hostname")
+		.ok()
+		.or_else(|| env::var("HOSTNAME").ok())
+		.or_else(|| env::var("COMPUTERNAME").ok())
+		.map(|v| v.trim().to_string())
+		.filter(|v| !v.is_empty())
+}
+
+fn uptime() -> Option<Duration> {
+	let uptime = fs::read_to_string("/proc/uptime").ok()?;
+	let seconds = uptime.split_whitespace().next()?.parse::<f64>().ok()?;
+
+	Some(Duration::from_secs_f64(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::TimeZone;
+
+	use super::*;
+
+	fn ctx() -> TemplateContext {
+		TemplateContext {
+			now: Local.with_ymd_and_hms(2026, 3, 4, 5, 6, 7).unwrap(),
+			hostname: Some(String::from("workstation")),
+			uptime: Some(Duration::from_secs(90_061)),
+		}
+	}
+
+	fn unavailable() -> TemplateContext {
+		TemplateContext {
+			hostname: None,
+			uptime: None,
+			..ctx()
+		}
+	}
+
+	#[test]
+	fn time() {
+		assert_eq!(render("at {time}", &ctx()), Ok(String::from("at 05:06")));
+	}
+
+	#[test]
+	fn date() {
+		assert_eq!(render("{date}", &ctx()), Ok(String::from("2026-03-04")));
+	}
+
+	#[test]
+	fn hostname() {
+		assert_eq!(
+			render("on {hostname}", &ctx()),
+			Ok(String::from("on workstation"))
+		);
+	}
+
+	#[test]
+	fn uptime() {
+		assert_eq!(
+			render("up {uptime}", &ctx()),
+			Ok(String::from("up 1d 1h 1m"))
+		);
+	}
+
+	#[test]
+	fn uptime_units() {
+		assert_eq!(format_duration(Duration::from_secs(7_380)), "2h 3m");
+		assert_eq!(format_duration(Duration::from_secs(2_700)), "45m");
+	}
+
+	#[test]
+	fn env() {
+		// cargo sets this for the test binary too
+		assert_eq!(
+			render("{env:CARGO_PKG_NAME}", &ctx()),
+			Ok(String::from(env!("CARGO_PKG_NAME")))
+		);
+	}
+
+	#[test]
+	fn whitespace_inside_braces() {
+		assert_eq!(render("{ time }", &ctx()), Ok(String::from("05:06")));
+	}
+
+	#[test]
+	fn escaped_braces() {
+		assert_eq!(
+			render("{{time}} is {time}", &ctx()),
+			Ok(String::from("{time} is 05:06"))
+		);
+		assert_eq!(render("a }} b", &ctx()), Ok(String::from("a } b")));
+	}
+
+	#[test]
+	fn escaped_text_renders_unchanged() {
+		let text = "fn main() { {time} }}";
+		assert_eq!(render(&escape(text), &ctx()), Ok(String::from(text)));
+	}
+
+	#[test]
+	fn unknown_variable() {
+		assert_eq!(
+			render("{nope}", &ctx()),
+			Err(TemplateError::UnknownVariable(String::from("nope")))
+		);
+	}
+
+	#[test]
+	fn unclosed() {
+		assert_eq!(render("ab {time", &ctx()), Err(TemplateError::Unclosed(3)));
+	}
+
+	#[test]
+	fn unmatched_close() {
+		assert_eq!(
+			render("ab } c", &ctx()),
+			Err(TemplateError::UnmatchedClose(3))
+		);
+	}
+
+	#[test]
+	fn missing_env() {
+		assert_eq!(
+			render("{env:DISCORD_PRESENCE_UNSET_VARIABLE}", &ctx()),
+			Err(TemplateError::MissingEnv(String::from(
+				"DISCORD_PRESENCE_UNSET_VARIABLE"
+			)))
+		);
+	}
+
+	#[test]
+	fn unavailable_values() {
+		assert_eq!(
+			render("{hostname}", &unavailable()),
+			Err(TemplateError::Unavailable("hostname"))
+		);
+		assert_eq!(
+			render("{uptime}", &unavailable()),
+			Err(TemplateError::Unavailable("uptime"))
+		);
+	}
+
+	#[test]
+	fn activity_text_fields() {
+		let activity = Activity {
+			details: Some(String::from("{hostname}")),
+			state: Some(String::from("{date}")),
+			large_key: Some(String::from("{time}")),
+			..Default::default()
+		};
+		let rendered = render_activity(&activity, &ctx()).unwrap();
+
+		assert!(has_variables(&activity));
+		assert!(!has_variables(&rendered));
+		assert_eq!(rendered.details.as_deref(), Some("workstation"));
+		assert_eq!(rendered.state.as_deref(), Some("2026-03-04"));
+		// keys are sent as is
+		assert_eq!(rendered.large_key.as_deref(), Some("{time}"));
+	}
+}
@@ -1,34 +1,67 @@
-use std::path::Path;
+#[cfg(target_os = "linux")]
+use std::time::Duration;
+use std::{
+	path::{Path, PathBuf},
+	sync::mpsc::Receiver,
+};
 
 use iced::futures::{SinkExt, channel::mpsc::UnboundedSender};
 use log::error;
 use tokio::task;
 use tray_icon::{
 	TrayIconBuilder, TrayIconEvent,
-	menu::{AboutMetadata, Menu, MenuEvent, MenuItem, PredefinedMenuItem},
+	menu::{AboutMetadata, Menu, MenuEvent, MenuItem, PredefinedMenuItem, Submenu},
 };
 
+use crate::{activity::Activity, settings};
+
+const PRESET_ID_PREFIX: &str = "preset:";
+#[cfg(target_os = "linux")]
+const PRESETS_POLL_INTERVAL: Duration = Duration::from_secs(2);
+#[cfg(target_os = "linux")]
+const UPDATES_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
 #[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
 pub enum TrayMessage {
 	Err(String),
 	TrayIcon(TrayIconEvent),
+	SetActivity(Activity),
+	ClearPresence,
+	ToggleConnection,
+	ToggleRotation,
+	TogglePomodoro,
 	Exit,
 	Open,
 }
 
+#[derive(Debug, Clone)]
+pub enum TrayUpdate {
+	ConnectionLabel(&'static str),
+	RotationLabel(&'static str),
+	PomodoroLabel(&'static str),
+	/// Shown next to the icon and as its tooltip
+	Status(Option<String>),
+}
+
 pub struct Tray {
 	send: UnboundedSender<TrayMessage>,
 	close: bool,
 }
 
 impl Tray {
-	pub fn spawn_thread(send: UnboundedSender<TrayMessage>) {
+	pub fn spawn_thread(send: UnboundedSender<TrayMessage>, updates: Receiver<TrayUpdate>) {
 		std::thread::spawn(move || {
 			#[cfg(target_os = "linux")]
 			gtk::init().expect("Failed to init gtk");
 
 			let menu = Menu::new();
+			let presets = Submenu::new("Presets", true);
 			let open = MenuItem::with_id("open", "Open", true, None);
+			let clear = MenuItem::with_id("clear", "Clear presence", true, None);
+			let connection = MenuItem::with_id("connection", "Connect", true, None);
+			let rotation = MenuItem::with_id("rotation", "Start rotation", true, None);
+			let pomodoro = MenuItem::with_id("pomodoro", "Start focus timer", true, None);
 			let exit = MenuItem::with_id("exit", "Exit", true, None);
 			menu.append_items(&[
 				&PredefinedMenuItem::about(
@@ -39,12 +72,21 @@ impl Tray {
 					}),
 				),
 				&PredefinedMenuItem::separator(),
+				&presets,
+				&clear,
+				&connection,
+				&rotation,
+				&pomodoro,
+				&PredefinedMenuItem::separator(),
 				&open,
 				&exit,
 			])
 			.expect("Failed to create tray menu");
 
-			let _tray_icon = TrayIconBuilder::new()
+			let preset_files = find_presets();
+			fill_presets_menu(&presets, &preset_files);
+
+			let tray_icon = TrayIconBuilder::new()
 				.with_title("Discord presence")
 				.with_icon(load_icon(Path::new("./icon.png")))
 				.with_menu(Box::new(menu))
@@ -52,7 +94,13 @@ impl Tray {
 				.expect("Failed to create tray");
 
 			#[cfg(target_os = "linux")]
-			gtk::main();
+			{
+				watch_presets(presets, preset_files);
+				watch_updates(tray_icon, connection, rotation, pomodoro, updates);
+				gtk::main();
+			}
+			#[cfg(not(target_os = "linux"))]
+			let _ = (tray_icon, pomodoro, updates);
 		});
 		task::spawn(async {
 			let mut state = Tray { send, close: false };
@@ -82,17 +130,112 @@ impl Tray {
 				"open" => {
 					self.send.send(TrayMessage::Open).await?;
 				}
+				"clear" => {
+					self.send.send(TrayMessage::ClearPresence).await?;
+				}
+				"connection" => {
+					self.send.send(TrayMessage::ToggleConnection).await?;
+				}
+				"rotation" => {
+					self.send.send(TrayMessage::ToggleRotation).await?;
+				}
+				"pomodoro" => {
+					self.send.send(TrayMessage::TogglePomodoro).await?;
+				}
 				"exit" => {
 					self.send.send(TrayMessage::Exit).await?;
 					self.close = true;
 				}
-				_ => {}
+				id => {
+					if let Some(path) = id.strip_prefix(PRESET_ID_PREFIX) {
+						let activity = Activity::read(path).await?;
+						self.send.send(TrayMessage::SetActivity(activity)).await?;
+					}
+				}
 			};
 		}
 		Ok(())
 	}
 }
 
+fn find_presets() -> Vec<PathBuf> {
+	let Some(entries) = settings::presets_dir().and_then(|dir| std::fs::read_dir(dir).ok()) else {
+		return Vec::new();
+	};
+
+	let mut files: Vec<PathBuf> = entries
+		.filter_map(|entry| entry.ok())
+		.map(|entry| entry.path())
+		.filter(|path| path.extension().is_some_and(|ext| ext == "crp"))
+		.collect();
+	files.sort();
+
+	files
+}
+
+#[cfg(target_os = "linux")]
+fn watch_presets(menu: Submenu, mut files: Vec<PathBuf>) {
+	gtk::glib::timeout_add_local(PRESETS_POLL_INTERVAL, move || {
+		let current = find_presets();
+		if current != files {
+			fill_presets_menu(&menu, &current);
+			files = current;
+		}
+		gtk::glib::ControlFlow::Continue
+	});
+}
+
+#[cfg(target_os = "linux")]
+fn watch_updates(
+	tray_icon: tray_icon::TrayIcon,
+	connection: MenuItem,
+	rotation: MenuItem,
+	pomodoro: MenuItem,
+	updates: Receiver<TrayUpdate>,
+) {
+	gtk::glib::timeout_add_local(UPDATES_POLL_INTERVAL, move || {
+		while let Ok(update) = updates.try_recv() {
+			match update {
+				TrayUpdate::ConnectionLabel(label) => connection.set_text(label),
+				TrayUpdate::RotationLabel(label) => rotation.set_text(label),
+				TrayUpdate::PomodoroLabel(label) => pomodoro.set_text(label),
+				TrayUpdate::Status(status) => {
+					tray_icon.set_title(status.as_deref());
+					if let Err(err) = tray_icon.set_tooltip(status.as_deref()) {
+						error!("{err}");
+					}
+				}
+			}
+		}
+		gtk::glib::ControlFlow::Continue
+	});
+}
+
+fn fill_presets_menu(menu: &Submenu, files: &[PathBuf]) {
+	while menu.remove_at(0).is_some() {}
+
+	if files.is_empty() {
+		let _ = menu.append(&MenuItem::new("No presets found", false, None));
+		return;
+	}
+
+	for file in files {
+		let name = file
+			.file_stem()
+			.map(|v| v.to_string_lossy())
+			.unwrap_or_default();
+		let item = MenuItem::with_id(
+			format!("{PRESET_ID_PREFIX}{}", file.display()),
+			name,
+			true,
+			None,
+		);
+		if let Err(err) = menu.append(&item) {
+			error!("{err}");
+		}
+	}
+}
+
 fn load_icon(path: &std::path::Path) -> tray_icon::Icon {
 	let (icon_rgba, icon_width, icon_height) = {
 		let image = image::open(path)
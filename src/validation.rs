@@ -0,0 +1,389 @@
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::activity::{
+	Activity, ImageSource, ImageUrlError, MAX_IMAGE_KEY_LENGTH, validate_image_url,
+};
+
+/// Shortest text discord accepts for details, state and image texts
+pub const MIN_TEXT_LENGTH: usize = 2;
+pub const MAX_TEXT_LENGTH: usize = 128;
+pub const MAX_BUTTON_TEXT_LENGTH: usize = 32;
+pub const MAX_BUTTON_URL_LENGTH: usize = 512;
+const BUTTON_URL_SCHEMES: [&str; 2] = ["http://", "https://"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+	Details,
+	State,
+	Party,
+	LargeImage,
+	LargeText,
+	SmallImage,
+	SmallText,
+	ButtonText(u8),
+	ButtonUrl(u8),
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum FieldError {
+	#[error("Has to be at least {0} characters long")]
+	TooShort(usize),
+	#[error("Can be at most {0} characters long")]
+	TooLong(usize),
+	#[error("Button URLs have to start with http:// or https://")]
+	ButtonScheme,
+	#[error("Buttons need both a text and a URL")]
+	IncompleteButton,
+	#[error("Party size cant be larger than the maximum")]
+	PartyTooLarge,
+	#[error("Party size and maximum cant be negative")]
+	NegativeParty,
+	#[error(transparent)]
+	Image(#[from] ImageUrlError),
+}
+
+/// A problem with one field of an activity
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("{field}: {error}")]
+pub struct InvalidField {
+	pub field: Field,
+	pub error: FieldError,
+}
+
+/// Everything wrong with an activity, empty if discord would take it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Validation {
+	pub errors: Vec<InvalidField>,
+}
+
+impl fmt::Display for Field {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Field::Details => write!(f, "Details"),
+			Field::State => write!(f, "State"),
+			Field::Party => write!(f, "Party"),
+			Field::LargeImage => write!(f, "Big image"),
+			Field::LargeText => write!(f, "Big image text"),
+			Field::SmallImage => write!(f, "Small image"),
+			Field::SmallText => write!(f, "Small image text"),
+			Field::ButtonText(nr) => write!(f, "Button {nr} text"),
+			Field::ButtonUrl(nr) => write!(f, "Button {nr} URL"),
+		}
+	}
+}
+
+impl Validation {
+	pub fn is_valid(&self) -> bool {
+		self.errors.is_empty()
+	}
+	pub fn get(&self, field: Field) -> Option<&FieldError> {
+		self.errors
+			.iter()
+			.find(|v| v.field == field)
+			.map(|v| &v.error)
+	}
+	/// The first problem, if any
+	pub fn into_result(self) -> Result<(), InvalidField> {
+		match self.errors.into_iter().next() {
+			Some(err) => Err(err),
+			None => Ok(()),
+		}
+	}
+	fn check(&mut self, field: Field, res: Result<(), FieldError>) {
+		if let Err(error) = res {
+			self.errors.push(InvalidField { field, error });
+		}
+	}
+}
+
+/// Checks `activity` against the limits discord enforces, empty fields count as unset
+pub fn validate(activity: &Activity) -> Validation {
+	let mut validation = Validation::default();
+
+	validation.check(Field::Details, text(&activity.details));
+	validation.check(Field::State, text(&activity.state));
+	validation.check(Field::Party, party(activity.party_size, activity.party_max));
+	validation.check(
+		Field::LargeImage,
+		image(activity.large_source, &activity.large_key),
+	);
+	validation.check(Field::LargeText, text(&activity.large_text));
+	validation.check(
+		Field::SmallImage,
+		image(activity.small_source, &activity.small_key),
+	);
+	validation.check(Field::SmallText, text(&activity.small_text));
+
+	let buttons = [
+		(1, &activity.button1_text, &activity.button1_url),
+		(2, &activity.button2_text, &activity.button2_url),
+	];
+	for (nr, label, url) in buttons {
+		let (label, url) = (non_empty(label), non_empty(url));
+		match (label, url) {
+			(None, None) => {}
+			(Some(_), None) => {
+				validation.check(Field::ButtonUrl(nr), Err(FieldError::IncompleteButton))
+			}
+			(None, Some(_)) => {
+				validation.check(Field::ButtonText(nr), Err(FieldError::IncompleteButton))
+			}
+			(Some(label), Some(url)) => {
+				validation.check(
+					Field::ButtonText(nr),
+					length(label, 1, MAX_BUTTON_TEXT_LENGTH),
+				);
+				validation.check(Field::ButtonUrl(nr), button_url(url));
+			}
+		}
+	}
+
+	validation
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+	value.as_deref().filter(|v| !v.is_empty())
+}
+
+fn length(value: &str, min: usize, max: usize) -> Result<(), FieldError> {
+	// discord counts characters, not bytes
+	let len = value.chars().count();
+	if len < min {
+		return Err(FieldError::TooShort(min));
+	}
+	if len > max {
+		return Err(FieldError::TooLong(max));
+	}
+
+	Ok(())
+}
+
+fn text(value: &Option<String>) -> Result<(), FieldError> {
+	match non_empty(value) {
+		Some(value) => length(value, MIN_TEXT_LENGTH, MAX_TEXT_LENGTH),
+		None => Ok(()),
+	}
+}
+
+fn image(source: ImageSource, key: &Option<String>) -> Result<(), FieldError> {
+	match (source, non_empty(key)) {
+		(_, None) => Ok(()),
+		(ImageSource::Url, Some(url)) => Ok(validate_image_url(url)?),
+		(ImageSource::Asset, Some(key)) => length(key, 1, MAX_IMAGE_KEY_LENGTH),
+	}
+}
+
+fn button_url(url: &str) -> Result<(), FieldError> {
+	if !BUTTON_URL_SCHEMES.iter().any(|scheme| {
+		url.strip_prefix(scheme)
+			.is_some_and(|rest| !rest.is_empty())
+	}) {
+		return Err(FieldError::ButtonScheme);
+	}
+
+	length(url, 1, MAX_BUTTON_URL_LENGTH)
+}
+
+fn party(size: Option<i32>, max: Option<i32>) -> Result<(), FieldError> {
+	if size.is_some_and(|v| v < 0) || max.is_some_and(|v| v < 0) {
+		return Err(FieldError::NegativeParty);
+	}
+	// only sent when both are set
+	match (size, max) {
+		(Some(size), Some(max)) if size > max => Err(FieldError::PartyTooLarge),
+		_ => Ok(()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn some(value: &str) -> Option<String> {
+		Some(value.to_string())
+	}
+
+	fn errors(activity: &Activity) -> Vec<(Field, FieldError)> {
+		validate(activity)
+			.errors
+			.into_iter()
+			.map(|v| (v.field, v.error))
+			.collect()
+	}
+
+	#[test]
+	fn empty_activity_is_valid() {
+		assert!(validate(&Activity::default()).is_valid());
+	}
+
+	#[test]
+	fn empty_strings_count_as_unset() {
+		let activity = Activity {
+			details: some(""),
+			button1_text: some(""),
+			button1_url: some(""),
+			large_key: some(""),
+			..Default::default()
+		};
+
+		assert!(validate(&activity).is_valid());
+	}
+
+	#[test]
+	fn text_length() {
+		let activity = Activity {
+			details: some("a"),
+			state: some(&"a".repeat(MAX_TEXT_LENGTH + 1)),
+			large_text: some("ab"),
+			small_text: some(&"a".repeat(MAX_TEXT_LENGTH)),
+			..Default::default()
+		};
+
+		assert_eq!(
+			errors(&activity),
+			[
+				(Field::Details, FieldError::TooShort(MIN_TEXT_LENGTH)),
+				(Field::State, FieldError::TooLong(MAX_TEXT_LENGTH)),
+			]
+		);
+	}
+
+	#[test]
+	fn length_counts_characters() {
+		// 4 bytes each, but discord only sees 128 characters
+		let emoji = "🎮".repeat(MAX_TEXT_LENGTH);
+		assert!(emoji.len() > MAX_TEXT_LENGTH);
+		assert_eq!(length(&emoji, MIN_TEXT_LENGTH, MAX_TEXT_LENGTH), Ok(()));
+		assert_eq!(
+			length("é", MIN_TEXT_LENGTH, MAX_TEXT_LENGTH),
+			Err(FieldError::TooShort(MIN_TEXT_LENGTH))
+		);
+	}
+
+	#[test]
+	fn button_label_too_long() {
+		let activity = Activity {
+			button1_text: some(&"a".repeat(MAX_BUTTON_TEXT_LENGTH + 1)),
+			button1_url: some("https://example.com"),
+			button2_text: some(&"a".repeat(MAX_BUTTON_TEXT_LENGTH)),
+			button2_url: some("https://example.com"),
+			..Default::default()
+		};
+
+		assert_eq!(
+			errors(&activity),
+			[(
+				Field::ButtonText(1),
+				FieldError::TooLong(MAX_BUTTON_TEXT_LENGTH)
+			)]
+		);
+	}
+
+	#[test]
+	fn button_url_scheme() {
+		for url in [
+			"ftp://example.com",
+			"example.com",
+			"https://",
+			"mp:attachments/1",
+		] {
+			let activity = Activity {
+				button1_text: some("Website"),
+				button1_url: some(url),
+				..Default::default()
+			};
+
+			assert_eq!(
+				errors(&activity),
+				[(Field::ButtonUrl(1), FieldError::ButtonScheme)],
+				"{url}"
+			);
+		}
+		assert_eq!(button_url("http://example.com"), Ok(()));
+	}
+
+	#[test]
+	fn incomplete_buttons() {
+		let activity = Activity {
+			button1_text: some("Website"),
+			button2_url: some("https://example.com"),
+			..Default::default()
+		};
+
+		assert_eq!(
+			errors(&activity),
+			[
+				(Field::ButtonUrl(1), FieldError::IncompleteButton),
+				(Field::ButtonText(2), FieldError::IncompleteButton),
+			]
+		);
+	}
+
+	#[test]
+	fn party() {
+		let party = |size, max| {
+			let activity = Activity {
+				party_size: size,
+				party_max: max,
+				..Default::default()
+			};
+			validate(&activity).get(Field::Party).cloned()
+		};
+
+		assert_eq!(party(Some(5), Some(4)), Some(FieldError::PartyTooLarge));
+		assert_eq!(party(Some(-1), Some(4)), Some(FieldError::NegativeParty));
+		assert_eq!(party(None, Some(-1)), Some(FieldError::NegativeParty));
+		assert_eq!(party(Some(4), Some(4)), None);
+		// the size alone isnt sent, so it cant be too large
+		assert_eq!(party(Some(5), None), None);
+	}
+
+	#[test]
+	fn images_by_source() {
+		let url = "https://example.com/cover.png";
+		let activity = Activity {
+			large_key: some(url),
+			large_source: ImageSource::Asset,
+			small_key: some("https://"),
+			small_source: ImageSource::Url,
+			..Default::default()
+		};
+
+		// an asset key is only checked for its length, even when it looks like a URL
+		assert_eq!(
+			errors(&activity),
+			[(Field::SmallImage, FieldError::Image(ImageUrlError::NoHost))]
+		);
+		assert_eq!(image(ImageSource::Url, &some(url)), Ok(()));
+		assert_eq!(
+			image(ImageSource::Url, &some("cover")),
+			Err(FieldError::Image(ImageUrlError::Scheme))
+		);
+		assert_eq!(
+			image(
+				ImageSource::Asset,
+				&some(&"a".repeat(MAX_IMAGE_KEY_LENGTH + 1))
+			),
+			Err(FieldError::TooLong(MAX_IMAGE_KEY_LENGTH))
+		);
+	}
+
+	#[test]
+	fn into_result_returns_the_first_error() {
+		let activity = Activity {
+			details: some("a"),
+			state: some("b"),
+			..Default::default()
+		};
+
+		let err = validate(&activity).into_result().unwrap_err();
+		assert_eq!(err.field, Field::Details);
+		assert_eq!(
+			err.to_string(),
+			"Details: Has to be at least 2 characters long"
+		);
+		assert_eq!(validate(&Activity::default()).into_result(), Ok(()));
+	}
+}